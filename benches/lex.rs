@@ -0,0 +1,50 @@
+//! Lexer throughput on generated multi-megabyte inputs. Run with `cargo bench`.
+
+use std::time::Instant;
+use lab1::{Lex, Tok};
+
+// count the tokens in `src`, returning how many there were
+fn lex_all(src: &[u8]) -> usize {
+    let mut lex = Lex::new(src);
+    let mut count = 0;
+    while !matches!(lex.next().tok, Tok::Empty) {
+        count += 1;
+    }
+    assert!(lex.problems.is_empty(), "benchmark input should lex cleanly");
+    count
+}
+
+fn bench(name: &str, src: &[u8]) {
+    const RUNS: u32 = 5;
+    let tokens = lex_all(src); // warm up
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        std::hint::black_box(lex_all(std::hint::black_box(src)));
+    }
+    let secs = start.elapsed().as_secs_f64() / RUNS as f64;
+    let mb = src.len() as f64 / (1024.0 * 1024.0);
+    println!("{:<16} {:>7.2} MB {:>9} tokens {:>9.2} ms {:>8.1} MB/s", name, mb, tokens, secs * 1000.0, mb / secs);
+}
+
+fn main() {
+    let program = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/code.txt")).unwrap();
+    let mut code = Vec::new();
+    while code.len() < 8 << 20 {
+        code.extend_from_slice(&program);
+        code.extend_from_slice(b"/* block comment */ x = 0x1F + 1_000 * \"text\\n\"; # line comment\n");
+    }
+    bench("program text", &code);
+
+    // runs of trivia that used to cost one stack frame per byte
+    let mut blank = vec![b'\n'; 500_000];
+    blank.extend_from_slice(b"func");
+    bench("blank lines", &blank);
+
+    let comments = b"# a comment line that goes on for a while\n".repeat(200_000);
+    bench("line comments", &comments);
+
+    let mut block = b"/*".to_vec();
+    block.extend(b" * inside one big block comment\n".repeat(200_000));
+    block.extend_from_slice(b"*/");
+    bench("block comment", &block);
+}
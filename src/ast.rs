@@ -0,0 +1,115 @@
+//! The program as the parser understands it, before any IR is chosen for it.
+//! Every node keeps the span of the source it came from so later passes can point at it.
+
+use crate::lex::Span;
+
+pub struct Program {
+    /// file-scope `int` and `const` declarations, in source order
+    pub globals: Vec<Stmt>,
+    pub functions: Vec<Function>,
+}
+
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Param>,
+    /// `-> void`; without one a function returns int
+    pub void: bool,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+pub struct Param {
+    pub name: String,
+    /// `int[] name`, a one-dimensional array of any size
+    pub array: bool,
+    pub span: Span,
+}
+
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+pub enum StmtKind {
+    /// `int[rows][cols] name;` or `int[size] name = {a, b, c};`.
+    /// Each size can be any constant expression. An initializer lists every element flat, in row-major order.
+    DeclareArray { name: String, dims: Vec<Expr>, init: Option<Vec<Expr>> },
+    /// `int a, b = 2, c;`
+    Declare(Vec<Declarator>),
+    /// `const N = 10;`, at file scope or in a block
+    Const { name: String, value: Expr },
+    Assign { place: Place, value: Expr },
+    /// `place op= value;`, and `++`/`--` with a value of 1
+    Update { place: Place, op: BinOp, value: Expr },
+    While { cond: Expr, body: Vec<Stmt> },
+    /// `for (init; cond; step) { body }`, where any of the three parts can be left out
+    For { init: Option<Box<Stmt>>, cond: Option<Expr>, step: Option<Box<Stmt>>, body: Vec<Stmt> },
+    /// an `else if` is an `otherwise` holding just the inner `If`
+    If { cond: Expr, then: Vec<Stmt>, otherwise: Option<Vec<Stmt>> },
+    Switch { value: Expr, cases: Vec<Case> },
+    Print(Expr),
+    PrintStr(Vec<u8>),
+    Read(Place),
+    /// `return;` has no value, which only a void function may do
+    Return(Option<Expr>),
+    Break,
+    Continue,
+    /// an expression evaluated for its side effects, e.g. `log(x);`
+    Expr(Expr),
+}
+
+/// One variable of a declaration, with its initial value if it has one.
+pub struct Declarator {
+    pub name: String,
+    pub value: Option<Expr>,
+    pub span: Span,
+}
+
+/// One `case N:` or `default:` label of a switch and the statements up to the next one.
+/// Control falls through into the next case unless it `break`s.
+pub struct Case {
+    /// a constant expression, None for `default`
+    pub value: Option<Expr>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+/// Something that can be stored to: a variable or one element of an array.
+pub struct Place {
+    pub name: String,
+    /// one per dimension, empty for a variable
+    pub indices: Vec<Expr>,
+    pub span: Span,
+}
+
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+pub enum ExprKind {
+    Number(i32),
+    Var(String),
+    Index(String, Vec<Expr>),
+    Call(String, Vec<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Mul,
+    Div,
+    Mod,
+    Add,
+    Sub,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
@@ -0,0 +1,539 @@
+use std::borrow::Cow;
+use std::io::{self, Read};
+
+/// A position in the source: 1-based line and column plus the 0-based byte offset.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pos {
+    pub line: usize,
+    pub col: usize,
+    pub offset: usize,
+}
+
+/// The source range a token covers, from its first byte up to (not including) `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.start.line, self.start.col)
+    }
+}
+
+/// An error tied to the token it was reported at.
+pub struct Diag {
+    pub span: Span,
+    pub msg: String,
+}
+
+/// Splits borrowed source into tokens. Identifiers and error text are slices of the source, never copies.
+pub struct Lex<'a> {
+    src: &'a [u8],
+    pos: Pos,
+    start: Pos,
+    pub problems: Vec<Diag>,
+    lossless: bool,
+}
+
+#[derive(Debug)]
+pub enum Tok<'a> {
+    Func,
+    Return,
+    Int,
+    Void,
+    Const,
+    Print,
+    Read,
+    While,
+    For,
+    If,
+    Else,
+    Switch,
+    Case,
+    Default,
+    Break,
+    Continue,
+    LeftParen,
+    RightParen,
+    LeftCurly,
+    RightCurly,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Semicolon,
+    Colon,
+    Arrow,
+    Plus,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulus,
+    Assign,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equality,
+    NotEqual,
+    And,
+    Or,
+    Not,
+    PlusAssign,
+    SubtractAssign,
+    MultiplyAssign,
+    DivideAssign,
+    ModulusAssign,
+    Increment,
+    Decrement,
+    Identifier(&'a [u8]),
+    /// The literal's value, which can be one more than `i32::MAX`: 2147483648 is only
+    /// accepted by the parser right after a `-`.
+    Number(u32),
+    Str(Cow<'a, [u8]>), // contents with escapes already decoded, borrowed when there were none
+    Error(&'a [u8]), // text the lexer couldn't make sense of, already reported in `Lex::problems`
+    Empty,
+}
+
+// how the token is spelled in source, as far as the token alone can tell
+impl std::fmt::Display for Tok<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            Tok::Func => "func",
+            Tok::Return => "return",
+            Tok::Int => "int",
+            Tok::Void => "void",
+            Tok::Const => "const",
+            Tok::Print => "print",
+            Tok::Read => "read",
+            Tok::While => "while",
+            Tok::For => "for",
+            Tok::If => "if",
+            Tok::Else => "else",
+            Tok::Switch => "switch",
+            Tok::Case => "case",
+            Tok::Default => "default",
+            Tok::Break => "break",
+            Tok::Continue => "continue",
+            Tok::LeftParen => "(",
+            Tok::RightParen => ")",
+            Tok::LeftCurly => "{",
+            Tok::RightCurly => "}",
+            Tok::LeftBracket => "[",
+            Tok::RightBracket => "]",
+            Tok::Comma => ",",
+            Tok::Semicolon => ";",
+            Tok::Colon => ":",
+            Tok::Arrow => "->",
+            Tok::Plus => "+",
+            Tok::Subtract => "-",
+            Tok::Multiply => "*",
+            Tok::Divide => "/",
+            Tok::Modulus => "%",
+            Tok::Assign => "=",
+            Tok::Less => "<",
+            Tok::LessEqual => "<=",
+            Tok::Greater => ">",
+            Tok::GreaterEqual => ">=",
+            Tok::Equality => "==",
+            Tok::NotEqual => "!=",
+            Tok::And => "&&",
+            Tok::Or => "||",
+            Tok::Not => "!",
+            Tok::PlusAssign => "+=",
+            Tok::SubtractAssign => "-=",
+            Tok::MultiplyAssign => "*=",
+            Tok::DivideAssign => "/=",
+            Tok::ModulusAssign => "%=",
+            Tok::Increment => "++",
+            Tok::Decrement => "--",
+            Tok::Identifier(text) | Tok::Error(text) => return write!(f, "{}", String::from_utf8_lossy(text)),
+            Tok::Number(num) => return write!(f, "{}", num),
+            Tok::Str(text) => return write!(f, "{:?}", String::from_utf8_lossy(text)),
+            Tok::Empty => "end of input",
+        };
+        f.write_str(text)
+    }
+}
+
+pub struct Token<'a> {
+    pub tok: Tok<'a>,
+    pub span: Span,
+    pub trivia: Option<Trivia<'a>>,
+}
+
+/// The exact source bytes behind a token, only produced by a lossless lexer.
+/// Concatenating `leading`, `text` and `trailing` over every token up to and including
+/// `Tok::Empty` gives back the original input byte for byte.
+pub struct Trivia<'a> {
+    /// whitespace and comments before the token that aren't some earlier token's trailing trivia
+    pub leading: &'a [u8],
+    pub text: &'a [u8],
+    /// spaces, tabs and `#` comments after the token, up to and including the end of its line
+    pub trailing: &'a [u8],
+}
+
+/// Read a whole program from any byte source, e.g. a file, stdin or a socket, so it can be
+/// handed to `Lex::new`. This is how the compiler is driven from other programs: the lexer
+/// borrows its input, so the source is read to the end before lexing starts rather than
+/// streamed token by token.
+pub fn read_source<R: Read>(mut source: R) -> io::Result<Vec<u8>> {
+    let mut src = Vec::new();
+    source.read_to_end(&mut src)?;
+    Ok(src)
+}
+
+impl<'a> Lex<'a> {
+    pub fn new(src: &'a [u8]) -> Lex<'a> {
+        Lex{
+            src,
+            pos: Pos { line: 1, col: 1, offset: 0 },
+            start: Pos { line: 1, col: 1, offset: 0 },
+            problems: Vec::new(),
+            lossless: false,
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(src: &'a str) -> Lex<'a> {
+        Lex::new(src.as_bytes())
+    }
+
+    /// The source bytes a span covers, e.g. a token's exact spelling.
+    pub fn source(&self, span: Span) -> &'a [u8] {
+        &self.src[span.start.offset..span.end.offset]
+    }
+
+    /// Keep comments and whitespace, handing them out with the tokens as `Trivia`.
+    pub fn lossless(mut self) -> Lex<'a> {
+        self.lossless = true;
+        self
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Token<'a> {
+        let begin = self.pos.offset;
+        self.skip_trivia();
+        self.start = self.pos;
+        let tok = self.lex();
+        let span = Span { start: self.start, end: self.pos };
+        let trivia = if self.lossless {
+            self.trailing_trivia();
+            Some(Trivia {
+                leading: &self.src[begin..span.start.offset],
+                text: &self.src[span.start.offset..span.end.offset],
+                trailing: &self.src[span.end.offset..self.pos.offset],
+            })
+        } else {
+            None
+        };
+        Token { tok, span, trivia }
+    }
+
+    // lex whatever input is left so its problems get reported too
+    pub fn finish(&mut self) {
+        while !matches!(self.next().tok, Tok::Empty) { /* nop */ }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.get(self.pos.offset).copied()
+    }
+
+    fn peek2(&self) -> Option<u8> {
+        self.src.get(self.pos.offset + 1).copied()
+    }
+
+    // consume one byte, keeping the position up to date. A lone '\r' counts as a line break, "\r\n" counts once.
+    fn bump(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos.offset += 1;
+        let newline = match byte {
+            b'\n' => true,
+            b'\r' => self.peek() != Some(b'\n'),
+            _ => false,
+        };
+        if newline {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+        Some(byte)
+    }
+
+    // consume `len` bytes known to contain no line breaks
+    fn skip(&mut self, len: usize) {
+        self.pos.offset += len;
+        self.pos.col += len;
+    }
+
+    // how many bytes from the current position on satisfy `pred`
+    fn count_while(&self, pred: impl Fn(u8) -> bool) -> usize {
+        self.src[self.pos.offset..].iter().take_while(|&&byte| pred(byte)).count()
+    }
+
+    // the source from `start` up to the current position
+    fn text(&self) -> &'a [u8] {
+        &self.src[self.start.offset..self.pos.offset]
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ') | Some(b'\t') | Some(b'\r') | Some(b'\n') => { self.bump(); },
+                Some(b'#') => self.ignore_line(),
+                Some(b'/') if self.peek2() == Some(b'*') => self.block_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    // consume the rest of the line after a token if it's only blanks and a comment
+    fn trailing_trivia(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ') | Some(b'\t') => { self.bump(); },
+                Some(b'#') => self.ignore_line(),
+                Some(b'\r') => {
+                    self.bump();
+                    if self.peek() == Some(b'\n') { self.bump(); }
+                    break;
+                },
+                Some(b'\n') => { self.bump(); break; },
+                _ => break,
+            }
+        }
+    }
+
+    fn ignore_line(&mut self) {
+        let len = self.count_while(|byte| byte != b'\n' && byte != b'\r');
+        self.skip(len);
+    }
+
+    // skip a /* */ comment starting at the current position. Comments nest.
+    fn block_comment(&mut self) {
+        let open = self.pos;
+        self.skip(2);
+        let mut depth = 1;
+        while let Some(byte) = self.bump() {
+            match (byte, self.peek()) {
+                (b'/', Some(b'*')) => { self.bump(); depth += 1; },
+                (b'*', Some(b'/')) => {
+                    self.bump();
+                    depth -= 1;
+                    if depth == 0 { return; }
+                },
+                _ => {},
+            }
+        }
+        let end = Pos { col: open.col + 2, offset: open.offset + 2, ..open };
+        self.problems.push(Diag { span: Span { start: open, end }, msg: "Lexer: unterminated block comment".to_string() });
+    }
+
+    fn token(&mut self, token: Tok<'a>) -> Tok<'a> {
+        self.skip(1);
+        token
+    }
+
+    fn token2(&mut self, token: Tok<'a>, peek_check: u8, token2: Tok<'a>) -> Tok<'a> {
+        if self.peek2() == Some(peek_check) {
+            self.skip(2);
+            token2
+        } else {
+            self.skip(1);
+            token
+        }
+    }
+
+    // operators like && that only exist with the character twice
+    fn doubled(&mut self, ch: u8, token: Tok<'a>) -> Tok<'a> {
+        if self.peek2() == Some(ch) {
+            self.skip(2);
+            token
+        } else {
+            self.skip(1);
+            self.error(format!("Lexer: found invalid char {}", ch as char))
+        }
+    }
+
+    // integer literals: decimal, 0x hex, 0b binary or 0o octal, with '_' allowed between digits.
+    // One past i32::MAX is let through so that -2147483648 can be written.
+    // Any letters or digits glued onto the literal are taken with it so `12ab` is one bad literal, not two tokens.
+    fn lex_number(&mut self) -> Tok<'a> {
+        let len = self.count_while(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+        self.skip(len);
+        let text = self.text();
+
+        let (radix, kind, digits) = match text {
+            [b'0', b'x' | b'X', rest @ ..] => (16, "hexadecimal ", rest),
+            [b'0', b'b' | b'B', rest @ ..] => (2, "binary ", rest),
+            [b'0', b'o' | b'O', rest @ ..] => (8, "octal ", rest),
+            _ => (10, "", text),
+        };
+        let msg = if digits.first() == Some(&b'_') || digits.last() == Some(&b'_') {
+            "has a '_' that doesn't separate digits"
+        } else {
+            // the text is all ASCII, so this can't fail
+            let digits = std::str::from_utf8(digits).unwrap_or_default();
+            let value = if digits.contains('_') {
+                u32::from_str_radix(&digits.replace('_', ""), radix)
+            } else {
+                u32::from_str_radix(digits, radix)
+            };
+            match value {
+                Ok(value) if value <= 1 << 31 => return Tok::Number(value),
+                Ok(_) => "does not fit in a 32-bit int",
+                Err(err) => match err.kind() {
+                    std::num::IntErrorKind::PosOverflow => "does not fit in a 32-bit int",
+                    std::num::IntErrorKind::Empty => "has no digits",
+                    _ => "has an invalid digit",
+                },
+            }
+        };
+        self.error(format!("Lexer: {}integer literal {} {}", kind, String::from_utf8_lossy(text), msg))
+    }
+
+    // a double-quoted string on a single line. Escapes: \n \t \" \\ and \xHH.
+    fn lex_string(&mut self) -> Tok<'a> {
+        self.skip(1);
+        let plain = self.count_while(|byte| !matches!(byte, b'"' | b'\\' | b'\n' | b'\r'));
+        self.skip(plain);
+        if self.peek() == Some(b'"') {
+            self.skip(1);
+            let text = self.text();
+            return Tok::Str(Cow::Borrowed(&text[1..text.len() - 1]));
+        }
+
+        let mut value = self.text()[1..].to_vec();
+        let mut bad_escape = None;
+        loop {
+            match self.peek() {
+                None | Some(b'\n') | Some(b'\r') => {
+                    return self.error(format!("Lexer: unterminated string {}", String::from_utf8_lossy(self.text())));
+                },
+                Some(b'"') => {
+                    self.skip(1);
+                    break;
+                },
+                Some(b'\\') => {
+                    let escape_start = self.pos.offset;
+                    self.skip(1);
+                    let decoded = match self.peek() {
+                        Some(b'n') => Some(b'\n'),
+                        Some(b't') => Some(b'\t'),
+                        Some(b'"') => Some(b'"'),
+                        Some(b'\\') => Some(b'\\'),
+                        Some(b'x') => None,
+                        Some(b'\n') | Some(b'\r') | None => {
+                            bad_escape.get_or_insert((escape_start, self.pos.offset));
+                            continue;
+                        },
+                        Some(_) => {
+                            self.skip(1);
+                            bad_escape.get_or_insert((escape_start, self.pos.offset));
+                            continue;
+                        },
+                    };
+                    self.skip(1);
+                    match decoded.or_else(|| self.hex_byte()) {
+                        Some(byte) => value.push(byte),
+                        None => { bad_escape.get_or_insert((escape_start, self.pos.offset)); },
+                    }
+                },
+                Some(byte) => {
+                    self.skip(1);
+                    value.push(byte);
+                },
+            }
+        }
+        if let Some((start, end)) = bad_escape {
+            let escape = String::from_utf8_lossy(&self.src[start..end]);
+            return self.error(format!("Lexer: invalid escape {} in string {}", escape, String::from_utf8_lossy(self.text())));
+        }
+        Tok::Str(Cow::Owned(value))
+    }
+
+    // the two hex digits of a \xHH escape, taking only as many as are valid
+    fn hex_byte(&mut self) -> Option<u8> {
+        let mut value = 0;
+        for _ in 0..2 {
+            let digit = self.peek().and_then(|byte| (byte as char).to_digit(16))?;
+            self.skip(1);
+            value = value * 16 + digit as u8;
+        }
+        Some(value)
+    }
+
+    fn lex_id(&mut self) -> Tok<'a> {
+        let len = self.count_while(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+        self.skip(len);
+
+        match self.text() {
+            b"func" => Tok::Func,
+            b"return" => Tok::Return,
+            b"int" => Tok::Int,
+            b"void" => Tok::Void,
+            b"const" => Tok::Const,
+            b"print" => Tok::Print,
+            b"read" => Tok::Read,
+            b"while" => Tok::While,
+            b"for" => Tok::For,
+            b"if" => Tok::If,
+            b"else" => Tok::Else,
+            b"switch" => Tok::Switch,
+            b"case" => Tok::Case,
+            b"default" => Tok::Default,
+            b"break" => Tok::Break,
+            b"continue" => Tok::Continue,
+            id => Tok::Identifier(id)
+        }
+    }
+
+    // record a problem covering the text since `start` and hand that text back as an error token
+    fn error(&mut self, msg: String) -> Tok<'a> {
+        self.problems.push(Diag { span: Span { start: self.start, end: self.pos }, msg });
+        Tok::Error(self.text())
+    }
+
+    // one token starting at the current position, which is past any trivia
+    fn lex(&mut self) -> Tok<'a> {
+        let Some(byte) = self.peek() else { return Tok::Empty };
+        match byte {
+            b'[' => self.token(Tok::LeftBracket),
+            b']' => self.token(Tok::RightBracket),
+            b'(' => self.token(Tok::LeftParen),
+            b')' => self.token(Tok::RightParen),
+            b'{' => self.token(Tok::LeftCurly),
+            b'}' => self.token(Tok::RightCurly),
+            b',' => self.token(Tok::Comma),
+            b';' => self.token(Tok::Semicolon),
+            b':' => self.token(Tok::Colon),
+            b'+' if self.peek2() == Some(b'+') => { self.skip(2); Tok::Increment },
+            b'-' if self.peek2() == Some(b'-') => { self.skip(2); Tok::Decrement },
+            b'-' if self.peek2() == Some(b'>') => { self.skip(2); Tok::Arrow },
+            b'+' => self.token2(Tok::Plus, b'=', Tok::PlusAssign),
+            b'-' => self.token2(Tok::Subtract, b'=', Tok::SubtractAssign),
+            b'*' => self.token2(Tok::Multiply, b'=', Tok::MultiplyAssign),
+            b'/' => self.token2(Tok::Divide, b'=', Tok::DivideAssign),
+            b'%' => self.token2(Tok::Modulus, b'=', Tok::ModulusAssign),
+            b'=' => self.token2(Tok::Assign,b'=',Tok::Equality),
+            b'<' => self.token2(Tok::Less, b'=',Tok::LessEqual),
+            b'>' => self.token2(Tok::Greater, b'=',Tok::GreaterEqual),
+            b'!' => self.token2(Tok::Not, b'=', Tok::NotEqual),
+            b'&' => self.doubled(b'&', Tok::And),
+            b'|' => self.doubled(b'|', Tok::Or),
+            b'A'..=b'Z' | b'a'..=b'z' | b'_' => { self.lex_id() }
+            b'0'..=b'9' => { self.lex_number() }
+            b'"' => { self.lex_string() }
+            _ => {
+                // take a whole UTF-8 sequence so the message shows the character, not a stray byte
+                self.skip(1);
+                let len = self.count_while(|byte| (0x80..=0xBF).contains(&byte));
+                self.skip(len);
+                let msg = format!("Lexer: found invalid char {}", String::from_utf8_lossy(self.text()));
+                self.error(msg)
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+#![allow(clippy::match_ref_pats)]
+
+pub mod ast;
+mod lex;
+mod lower;
+mod par;
+
+pub use lex::{read_source, Diag, Lex, Pos, Span, Tok, Token, Trivia};
+pub use lower::lower;
+pub use par::Par;
@@ -0,0 +1,745 @@
+use std::collections::{HashMap, HashSet};
+use crate::ast::{BinOp, Case, Expr, ExprKind, Function, Place, Program, Stmt, StmtKind};
+use crate::lex::{Diag, Span};
+
+#[derive(Clone)]
+enum Type {
+    Var,
+    Arr(Vec<i32>), // the size of each dimension, 0 when it isn't known like for an `int[]` parameter
+    Fn { params: Vec<Type>, void: bool },
+    Const(i32),
+}
+
+// what a name means in one scope, and what it's called in the IR
+struct Symbol {
+    kind: Type,
+    ir: String,
+}
+
+// quote a string for the IR, escaping it the same way the source language does
+fn ir_string(text: &[u8]) -> String {
+    let mut res = String::from("\"");
+    for &byte in text {
+        match byte {
+            b'\n' => res.push_str("\\n"),
+            b'\t' => res.push_str("\\t"),
+            b'"' => res.push_str("\\\""),
+            b'\\' => res.push_str("\\\\"),
+            b' '..=b'~' => res.push(byte as char),
+            _ => res.push_str(&format!("\\x{:02X}", byte)),
+        }
+    }
+    res.push('"');
+    res
+}
+
+/// Turn a parsed program into IR text, or every problem found along the way.
+///
+/// Globals are declared with `%int`/`%int[]` before the first `%func`. Their initial values are
+/// assigned at the very start of `main`, in source order, before main's own statements run.
+pub fn lower(program: &Program) -> Result<String, Vec<Diag>> {
+    let mut lower = Lower { out: String::new(), t_count: 0, l_count: 0, types: vec![HashMap::new()], storage: HashSet::new(), breaks: Vec::new(), continues: Vec::new(), func: String::new(), void: false, problems: Vec::new() };
+    for global in &program.globals {
+        lower.global(global);
+    }
+    let has_main = program.functions.iter().any(|function| function.name == "main");
+    if let Some(global) = program.globals.iter().find(|global| has_initializer(global)) {
+        if !has_main {
+            let msg = "global initial values are assigned at the start of main, but there is no main".to_string();
+            lower.problems.push(Diag { span: global.span, msg });
+        }
+    }
+    // every signature is known before any body is lowered, so calls can come before definitions
+    for function in &program.functions {
+        lower.signature(function);
+    }
+    for function in &program.functions {
+        let inits = if function.name == "main" { &program.globals[..] } else { &[] };
+        lower.function(function, inits);
+    }
+    if lower.problems.is_empty() { Ok(lower.out) } else { Err(lower.problems) }
+}
+
+fn param_types(function: &Function) -> Vec<Type> {
+    function.params.iter()
+        .map(|param| if param.array { Type::Arr(vec![0]) } else { Type::Var })
+        .collect()
+}
+
+fn has_initializer(global: &Stmt) -> bool {
+    match &global.kind {
+        StmtKind::DeclareArray { init, .. } => init.is_some(),
+        StmtKind::Declare(vars) => vars.iter().any(|var| var.value.is_some()),
+        _ => false,
+    }
+}
+
+struct Lower {
+    out: String,
+    t_count: usize,
+    l_count: usize,
+
+    types: Vec<HashMap<String, Symbol>>,
+    // every IR name given a `%int` or `%int[]` in the function being lowered, globals included.
+    // The IR has one flat namespace per function, so a declaration whose name is already
+    // taken, by a shadowed variable or one in an earlier block, gets a fresh IR name.
+    storage: HashSet<String>,
+    // the label `break` and `continue` jump to in each enclosing construct, innermost last.
+    // `while` loops push None: they still use the structured `break`/`continue` pseudo-ops.
+    // A switch only takes `break`.
+    breaks: Vec<Option<String>>,
+    continues: Vec<Option<String>>,
+    // the function being lowered and whether it returns void
+    func: String,
+    void: bool,
+    problems: Vec<Diag>,
+}
+
+impl Lower {
+    fn line(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn temp_name(&mut self) -> String {
+        let res = format!("temp{}", self.t_count);
+        self.line(&format!("%int {}", res));
+        self.t_count += 1;
+        res
+    }
+
+    fn label_name(&mut self) -> String {
+        let res = format!("label{}", self.l_count);
+        self.l_count += 1;
+        res
+    }
+
+    // declare a file-scope variable, array or constant in the outermost scope
+    fn global(&mut self, global: &Stmt) {
+        match &global.kind {
+            StmtKind::DeclareArray { name, dims, init } => {
+                let Some((dims, size)) = self.array_size(name, dims, global.span) else { return };
+                self.line(&format!("%int[] {}, {}", name, size));
+                self.declare_global(name, Type::Arr(dims), global.span);
+                if let Some(values) = init {
+                    self.check_initializer(name, size, values.len(), global.span);
+                }
+            },
+            StmtKind::Declare(vars) => {
+                for var in vars {
+                    self.line(&format!("%int {}", var.name));
+                    self.declare_global(&var.name, Type::Var, var.span);
+                }
+            },
+            StmtKind::Const { name, value } => {
+                if let Some(value) = self.constant(name, value) {
+                    self.declare_global(name, Type::Const(value), global.span);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // the initial values of globals, at the start of main
+    fn global_init(&mut self, global: &Stmt) {
+        match &global.kind {
+            StmtKind::DeclareArray { name, init: Some(values), .. } => {
+                for (i, value) in values.iter().enumerate() {
+                    let rhs = self.expr(value);
+                    self.line(&format!("%mov [{}+{}], {}", name, i, rhs));
+                }
+            },
+            StmtKind::Declare(vars) => {
+                for var in vars {
+                    if let Some(value) = &var.value {
+                        let rhs = self.expr(value);
+                        self.line(&format!("%mov {}, {}", var.name, rhs));
+                    }
+                }
+            },
+            _ => {},
+        }
+    }
+
+    fn declare_global(&mut self, name: &str, kind: Type, span: Span) {
+        if self.types[0].insert(name.to_string(), Symbol { kind, ir: name.to_string() }).is_some() {
+            self.problems.push(Diag { span, msg: format!("{} is already declared", name) });
+        }
+    }
+
+    // the value a `const` is defined as, which has to be known while compiling
+    fn constant(&mut self, name: &str, value: &Expr) -> Option<i32> {
+        self.known(value, &format!("the value of constant {}", name))
+    }
+
+    // evaluate an expression that `what` needs while compiling, reporting it if it can't be
+    fn known(&mut self, expr: &Expr, what: &str) -> Option<i32> {
+        match self.constant_value(expr) {
+            Ok(Some(value)) => Some(value),
+            Ok(None) => {
+                let msg = format!("{} has to be known while compiling, using only numbers and constants", what);
+                self.problems.push(Diag { span: expr.span, msg });
+                None
+            },
+            Err(problem) => {
+                self.problems.push(problem);
+                None
+            },
+        }
+    }
+
+    // a number known while compiling: literals and constants combined with any operator.
+    // None when part of it is only known when the program runs.
+    fn constant_value(&self, expr: &Expr) -> Result<Option<i32>, Diag> {
+        let overflow = || Diag { span: expr.span, msg: "constant expression overflows".to_string() };
+        let value = match &expr.kind {
+            ExprKind::Number(num) => *num,
+            ExprKind::Var(name) => match self.lookup(name) {
+                Some(Type::Const(value)) => *value,
+                _ => return Ok(None),
+            },
+            ExprKind::Neg(operand) => {
+                let Some(operand) = self.constant_value(operand)? else { return Ok(None) };
+                operand.checked_neg().ok_or_else(overflow)?
+            },
+            ExprKind::Not(operand) => {
+                let Some(operand) = self.constant_value(operand)? else { return Ok(None) };
+                (operand == 0) as i32
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let (Some(lhs), Some(rhs)) = (self.constant_value(lhs)?, self.constant_value(rhs)?) else { return Ok(None) };
+                if matches!(op, BinOp::Div | BinOp::Mod) && rhs == 0 {
+                    return Err(Diag { span: expr.span, msg: "division by zero in constant expression".to_string() });
+                }
+                match op {
+                    BinOp::Mul => lhs.checked_mul(rhs).ok_or_else(overflow)?,
+                    BinOp::Div => lhs.checked_div(rhs).ok_or_else(overflow)?,
+                    BinOp::Mod => lhs.checked_rem(rhs).ok_or_else(overflow)?,
+                    BinOp::Add => lhs.checked_add(rhs).ok_or_else(overflow)?,
+                    BinOp::Sub => lhs.checked_sub(rhs).ok_or_else(overflow)?,
+                    BinOp::Less => (lhs < rhs) as i32,
+                    BinOp::LessEqual => (lhs <= rhs) as i32,
+                    BinOp::Greater => (lhs > rhs) as i32,
+                    BinOp::GreaterEqual => (lhs >= rhs) as i32,
+                    BinOp::Equal => (lhs == rhs) as i32,
+                    BinOp::NotEqual => (lhs != rhs) as i32,
+                    BinOp::And => (lhs != 0 && rhs != 0) as i32,
+                    BinOp::Or => (lhs != 0 || rhs != 0) as i32,
+                }
+            },
+            ExprKind::Index(..) | ExprKind::Call(..) => return Ok(None),
+        };
+        Ok(Some(value))
+    }
+
+    // every dimension is laid out flat, row after row
+    fn array_size(&mut self, name: &str, dims: &[Expr], span: Span) -> Option<(Vec<i32>, i32)> {
+        let mut sizes = Vec::new();
+        for dim in dims {
+            let size = self.known(dim, &format!("the size of array {}", name))?;
+            if size < 1 {
+                self.problems.push(Diag { span: dim.span, msg: format!("array {} needs a positive size, not {}", name, size) });
+                return None
+            }
+            sizes.push(size);
+        }
+        let size = sizes.iter().try_fold(1i32, |size, &dim| size.checked_mul(dim));
+        if size.is_none() {
+            self.problems.push(Diag { span, msg: format!("array {} is too large", name) });
+        }
+        Some((sizes, size?))
+    }
+
+    fn check_initializer(&mut self, name: &str, size: i32, count: usize, span: Span) {
+        if count != size as usize {
+            let msg = format!("array {} has {} elements but {} initial values", name, size, count);
+            self.problems.push(Diag { span, msg });
+        }
+    }
+
+    // functions live in the outermost scope next to the globals
+    fn signature(&mut self, function: &Function) {
+        let kind = Type::Fn { params: param_types(function), void: function.void };
+        match self.types[0].insert(function.name.clone(), Symbol { kind, ir: function.name.clone() }).map(|symbol| symbol.kind) {
+            Some(Type::Fn { .. }) => {
+                self.problems.push(Diag { span: function.span, msg: format!("function {} is defined more than once", function.name) });
+            },
+            Some(_) => {
+                self.problems.push(Diag { span: function.span, msg: format!("{} is already declared", function.name) });
+            },
+            None => {},
+        }
+    }
+
+    fn function(&mut self, function: &Function, inits: &[Stmt]) {
+        self.storage = self.types[0].values()
+            .filter(|symbol| matches!(symbol.kind, Type::Var | Type::Arr(_)))
+            .map(|symbol| symbol.ir.clone())
+            .collect();
+        let names: Vec<String> = function.params.iter().map(|param| self.fresh(&param.name)).collect();
+        let params: Vec<String> = function.params.iter().zip(&names)
+            .map(|(param, ir)| format!("{} {}", if param.array { "%int[]" } else { "%int" }, ir))
+            .collect();
+        self.line(&format!("%func {}({})", function.name, params.join(", ")));
+
+        let types = param_types(function);
+        self.func = function.name.clone();
+        self.void = function.void;
+        for global in inits {
+            self.global_init(global);
+        }
+
+        self.types.push(HashMap::new());
+        for ((param, kind), ir) in function.params.iter().zip(types).zip(names) {
+            if let Some(_already_present) = self.types.last_mut().unwrap().insert(param.name.clone(), Symbol { kind, ir }) {
+                self.problems.push(Diag { span: param.span, msg: "duplicate parameter name".to_string() });
+            }
+        }
+        for stmt in &function.body {
+            self.statement(stmt);
+        }
+        self.types.pop();
+        // a void function can just run off its end
+        if function.void && !matches!(function.body.last(), Some(Stmt { kind: StmtKind::Return(_), .. })) {
+            self.line("%ret");
+        }
+        self.line("%endfunc");
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.types.push(HashMap::new());
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+        self.types.pop();
+    }
+
+    // a name not yet used for storage in this function: `name` itself, or `name_1`, `name_2`, ...
+    fn fresh(&mut self, name: &str) -> String {
+        let mut ir = name.to_string();
+        let mut n = 0;
+        while self.storage.contains(&ir) {
+            n += 1;
+            ir = format!("{}_{}", name, n);
+        }
+        self.storage.insert(ir.clone());
+        ir
+    }
+
+    // add a local to the innermost scope and return its IR name.
+    // Hiding a name from an enclosing scope is fine, declaring it twice in one scope isn't.
+    fn declare(&mut self, name: &str, kind: Type, span: Span) -> String {
+        let ir = match kind {
+            Type::Var | Type::Arr(_) => self.fresh(name),
+            _ => name.to_string(),
+        };
+        if self.types.last_mut().unwrap().insert(name.to_string(), Symbol { kind, ir: ir.clone() }).is_some() {
+            self.problems.push(Diag { span, msg: format!("{} is already declared", name) });
+        }
+        ir
+    }
+
+    // what a variable or array is called in the IR. Undeclared names are left as they are.
+    fn ir_name(&self, name: &str) -> String {
+        match self.types.iter().rev().find_map(|scope| scope.get(name)) {
+            Some(symbol) => symbol.ir.clone(),
+            None => name.to_string(),
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::DeclareArray { name, dims, init } => {
+                let Some((dims, size)) = self.array_size(name, dims, stmt.span) else { return };
+                let ir = self.declare(name, Type::Arr(dims), stmt.span);
+                self.line(&format!("%int[] {}, {}", ir, size));
+                let Some(values) = init else { return };
+                self.check_initializer(name, size, values.len(), stmt.span);
+                for (i, value) in values.iter().enumerate() {
+                    let rhs = self.expr(value);
+                    self.line(&format!("%mov [{}+{}], {}", ir, i, rhs));
+                }
+            },
+            StmtKind::Declare(vars) => {
+                for var in vars {
+                    // the initial value is computed first, so `int x = x + 1;` reads an outer x
+                    let rhs = var.value.as_ref().map(|value| self.expr(value));
+                    let ir = self.declare(&var.name, Type::Var, var.span);
+                    self.line(&format!("%int {}", ir));
+                    if let Some(rhs) = rhs {
+                        self.line(&format!("%mov {}, {}", ir, rhs));
+                    }
+                }
+            },
+            StmtKind::Const { name, value } => {
+                if let Some(value) = self.constant(name, value) {
+                    self.declare(name, Type::Const(value), stmt.span);
+                }
+            },
+            StmtKind::Assign { place, value } => {
+                let dest = self.place(place);
+                let rhs = self.expr(value);
+                self.line(&format!("%mov {}, {}", dest, rhs));
+            },
+            StmtKind::Update { place, op, value } if place.indices.is_empty() => {
+                self.assignable(&place.name, place.span);
+                let rhs = self.expr(value);
+                let ir = self.ir_name(&place.name);
+                self.line(&format!("%{} {}, {}, {}", op_name(*op), ir, ir, rhs));
+            },
+            StmtKind::Update { place, op, value } => {
+                // the element is read into a temp, so its index is only computed once
+                let dest = self.place(place);
+                let temp = self.temp_name();
+                self.line(&format!("%mov {}, {}", temp, dest));
+                let rhs = self.expr(value);
+                self.line(&format!("%{} {}, {}, {}", op_name(*op), temp, temp, rhs));
+                self.line(&format!("%mov {}, {}", dest, temp));
+            },
+            StmtKind::While { cond, body } => {
+                self.out.push_str("while(");
+                let cond = self.expr(cond);
+                self.line(&format!("cond({}))", cond));
+                self.breaks.push(None);
+                self.continues.push(None);
+                self.block(body);
+                self.breaks.pop();
+                self.continues.pop();
+            },
+            StmtKind::For { init, cond, step, body } => {
+                let begin = self.label_name();
+                let next = self.label_name();
+                let done = self.label_name();
+                self.types.push(HashMap::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                self.line(&format!(":{}", begin));
+                if let Some(cond) = cond {
+                    let cond = self.expr(cond);
+                    self.line(&format!("%branch_ifn {}, :{}", cond, done));
+                }
+                self.breaks.push(Some(done.clone()));
+                self.continues.push(Some(next.clone()));
+                self.block(body);
+                self.breaks.pop();
+                self.continues.pop();
+                self.line(&format!(":{}", next));
+                if let Some(step) = step {
+                    self.statement(step);
+                }
+                self.line(&format!("%jmp :{}", begin));
+                self.line(&format!(":{}", done));
+                self.types.pop();
+            },
+            StmtKind::Switch { value, cases } => self.switch(value, cases),
+            StmtKind::If { cond, then, otherwise } => {
+                self.out.push_str("if(");
+                let cond = self.expr(cond);
+                self.line(&format!("cond({}))", cond));
+                self.block(then);
+                if let Some(otherwise) = otherwise {
+                    self.out.push_str("else ");
+                    self.block(otherwise);
+                }
+            },
+            StmtKind::Print(value) => {
+                let value = self.expr(value);
+                self.line(&format!("%out {}", value));
+            },
+            StmtKind::PrintStr(text) => {
+                self.line(&format!("%outs {}", ir_string(text)));
+            },
+            StmtKind::Read(place) if place.indices.is_empty() => {
+                self.assignable(&place.name, place.span);
+                let ir = self.ir_name(&place.name);
+                self.line(&format!("%input {}", ir));
+            },
+            StmtKind::Read(place) => {
+                // %int temp28
+                // %input temp28
+                // %mov [arr + 1], temp28
+                let dest = self.place(place);
+                let temp = self.temp_name();
+                self.line(&format!("%input {}", temp));
+                self.line(&format!("%mov {}, {}", dest, temp));
+            },
+            StmtKind::Return(Some(value)) => {
+                if self.void {
+                    let msg = format!("{} returns void, so `return` can't have a value", self.func);
+                    self.problems.push(Diag { span: stmt.span, msg });
+                }
+                let value = self.expr(value);
+                self.line(&format!("%ret {}", value));
+            },
+            StmtKind::Return(None) => {
+                if !self.void {
+                    let msg = format!("{} returns int, so `return` needs a value", self.func);
+                    self.problems.push(Diag { span: stmt.span, msg });
+                }
+                self.line("%ret");
+            },
+            StmtKind::Break | StmtKind::Continue => {
+                let (word, targets) = match stmt.kind {
+                    StmtKind::Break => ("break", &self.breaks),
+                    _ => ("continue", &self.continues),
+                };
+                match targets.last().cloned() {
+                    Some(Some(label)) => self.line(&format!("%jmp :{}", label)),
+                    Some(None) => self.line(word),
+                    None => {
+                        let msg = format!("`{}` outside of a loop", word);
+                        self.problems.push(Diag { span: stmt.span, msg });
+                    },
+                }
+            },
+            StmtKind::Expr(value) => {
+                // a call still needs a temp to land in, even though nobody reads it
+                match &value.kind {
+                    ExprKind::Call(id, args) => { self.call(id, args, value.span, false); },
+                    _ => { self.expr(value); },
+                }
+            },
+        }
+    }
+
+    // compare against each case in turn and jump to the first that matches, or to `default`.
+    // The bodies follow each other in source order so control falls through like in C.
+    fn switch(&mut self, value: &Expr, cases: &[Case]) {
+        let value = self.expr(value);
+        let labels: Vec<String> = cases.iter().map(|_| self.label_name()).collect();
+        let done = self.label_name();
+        let mut seen = HashSet::new();
+        let mut default = &done;
+        for (case, label) in cases.iter().zip(&labels) {
+            let Some(case_value) = &case.value else { default = label; continue };
+            let Some(num) = self.known(case_value, "a case label") else { continue };
+            if !seen.insert(num) {
+                self.problems.push(Diag { span: case.span, msg: format!("duplicate case {}", num) });
+            }
+            let temp = self.temp_name();
+            self.line(&format!("%eq {}, {}, {}", temp, value, num));
+            self.line(&format!("%branch_if {}, :{}", temp, label));
+        }
+        self.line(&format!("%jmp :{}", default));
+
+        self.types.push(HashMap::new());
+        self.breaks.push(Some(done.clone()));
+        for (case, label) in cases.iter().zip(&labels) {
+            self.line(&format!(":{}", label));
+            for stmt in &case.body {
+                self.statement(stmt);
+            }
+        }
+        self.breaks.pop();
+        self.types.pop();
+        self.line(&format!(":{}", done));
+    }
+
+    // the operand that stores into `place`, with the code for its index already emitted
+    fn place(&mut self, place: &Place) -> String {
+        if place.indices.is_empty() {
+            self.assignable(&place.name, place.span);
+            return self.ir_name(&place.name);
+        }
+        self.element(&place.name, &place.indices, place.span)
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.types.iter().rev().find_map(|scope| scope.get(name)).map(|symbol| &symbol.kind)
+    }
+
+    // a whole array can only be passed to an `int[]` parameter, not used as a value
+    fn scalar(&mut self, name: &str, span: Span) {
+        if let Some(Type::Arr(_)) = self.lookup(name) {
+            self.problems.push(Diag { span, msg: format!("{} is an array, so it needs an index here", name) });
+        }
+    }
+
+    // a variable that's about to be stored to
+    fn assignable(&mut self, name: &str, span: Span) {
+        if let Some(Type::Const(_)) = self.lookup(name) {
+            self.problems.push(Diag { span, msg: format!("{} is a constant, so it can't be changed", name) });
+        }
+        self.scalar(name, span);
+    }
+
+    // `used` is whether the result goes anywhere, which a void function doesn't have
+    fn call(&mut self, id: &str, args: &[Expr], span: Span, used: bool) -> String {
+        // a local variable hides a function of the same name
+        let params = match self.lookup(id).cloned() {
+            Some(Type::Fn { params, void }) => {
+                if void && used {
+                    self.problems.push(Diag { span, msg: format!("{} returns void, so its result can't be used", id) });
+                }
+                Some(params)
+            },
+            Some(_) => {
+                self.problems.push(Diag { span, msg: format!("{} is not a function", id) });
+                None
+            },
+            None => {
+                self.problems.push(Diag { span, msg: format!("call to undefined function {}", id) });
+                None
+            },
+        };
+        if let Some(params) = &params {
+            if params.len() != args.len() {
+                let msg = format!("{} takes {} arguments but is called with {}", id, params.len(), args.len());
+                self.problems.push(Diag { span, msg });
+            }
+        }
+        let args: Vec<String> = args.iter().enumerate()
+            .map(|(i, arg)| self.argument(id, i, arg, params.as_ref().and_then(|params| params.get(i))))
+            .collect();
+        let temp = self.temp_name();
+        let mut call = format!("%call {}, {}(", temp, id);
+        for arg in &args {
+            call.push_str(&format!("{}, ", arg));
+        }
+        call.push(')');
+        self.line(&call);
+        temp
+    }
+
+    // an array argument is passed by name, anything else is evaluated like any expression
+    fn argument(&mut self, func: &str, i: usize, arg: &Expr, param: Option<&Type>) -> String {
+        let array = match &arg.kind {
+            ExprKind::Var(name) => match self.lookup(name) {
+                Some(Type::Arr(dims)) => Some((name.clone(), dims.len())),
+                _ => None,
+            },
+            _ => None,
+        };
+        match (param, array) {
+            (Some(Type::Arr(_)), Some((name, 1))) | (None, Some((name, _))) => self.ir_name(&name),
+            (Some(Type::Arr(_)), array) => {
+                let msg = format!("argument {} of {} has to be a one-dimensional array", i + 1, func);
+                self.problems.push(Diag { span: arg.span, msg });
+                match array {
+                    Some((name, _)) => self.ir_name(&name),
+                    None => self.expr(arg),
+                }
+            },
+            (_, Some((name, _))) => {
+                let msg = format!("argument {} of {} is an int, but {} is an array", i + 1, func, name);
+                self.problems.push(Diag { span: arg.span, msg });
+                self.ir_name(&name)
+            },
+            (_, None) => self.expr(arg),
+        }
+    }
+
+    // the `[arr+offset]` operand for one element, with the row-major offset computed from the indices
+    fn element(&mut self, name: &str, indices: &[Expr], span: Span) -> String {
+        let dims = match self.lookup(name) {
+            Some(Type::Arr(dims)) => dims.clone(),
+            Some(_) => {
+                self.problems.push(Diag { span, msg: format!("{} is not an array", name) });
+                return format!("[{}+0]", name);
+            },
+            None => {
+                self.problems.push(Diag { span, msg: format!("array {} is not declared", name) });
+                return format!("[{}+0]", name);
+            },
+        };
+        if dims.len() != indices.len() {
+            let plural = if dims.len() == 1 { "" } else { "s" };
+            let msg = format!("{} has {} dimension{} but is indexed with {}", name, dims.len(), plural, indices.len());
+            self.problems.push(Diag { span, msg });
+            return format!("[{}+0]", name);
+        }
+        let ir = self.ir_name(name);
+        let mut offset = self.expr(&indices[0]);
+        for (index, size) in indices[1..].iter().zip(&dims[1..]) {
+            let index = self.expr(index);
+            let scaled = self.temp_name();
+            self.line(&format!("%mult {}, {}, {}", scaled, offset, size));
+            let sum = self.temp_name();
+            self.line(&format!("%add {}, {}, {}", sum, scaled, index));
+            offset = sum;
+        }
+        format!("[{}+{}]", ir, offset)
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Number(num) => num.to_string(),
+            ExprKind::Var(id) => {
+                self.scalar(id, expr.span);
+                match self.lookup(id) {
+                    Some(Type::Const(value)) => value.to_string(),
+                    _ => self.ir_name(id),
+                }
+            },
+            ExprKind::Index(id, indices) => {
+                let element = self.element(id, indices, expr.span);
+                let temp = self.temp_name();
+                self.line(&format!("%mov {}, {}", temp, element));
+                temp
+            },
+            ExprKind::Call(id, args) => self.call(id, args, expr.span, true),
+            ExprKind::Not(operand) => {
+                let operand = self.expr(operand);
+                let temp = self.temp_name();
+                self.line(&format!("%eq {}, {}, 0", temp, operand));
+                temp
+            },
+            ExprKind::Neg(operand) => {
+                let operand = self.expr(operand);
+                let temp = self.temp_name();
+                self.line(&format!("%sub {}, 0, {}", temp, operand));
+                temp
+            },
+            // a || b and a && b only evaluate b when a didn't already decide the result
+            ExprKind::Binary(BinOp::Or, lhs, rhs) => {
+                let lhs = self.expr(lhs);
+                let temp = self.temp_name();
+                let done = self.label_name();
+                self.line(&format!("%mov {}, 1", temp));
+                self.line(&format!("%branch_if {}, :{}", lhs, done));
+                let rhs = self.expr(rhs);
+                self.line(&format!("%neq {}, {}, 0", temp, rhs));
+                self.line(&format!(":{}", done));
+                temp
+            },
+            ExprKind::Binary(BinOp::And, lhs, rhs) => {
+                let lhs = self.expr(lhs);
+                let temp = self.temp_name();
+                let done = self.label_name();
+                self.line(&format!("%mov {}, 0", temp));
+                let is_false = self.temp_name();
+                self.line(&format!("%eq {}, {}, 0", is_false, lhs));
+                self.line(&format!("%branch_if {}, :{}", is_false, done));
+                let rhs = self.expr(rhs);
+                self.line(&format!("%neq {}, {}, 0", temp, rhs));
+                self.line(&format!(":{}", done));
+                temp
+            },
+            ExprKind::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                let temp = self.temp_name();
+                self.line(&format!("%{} {}, {}, {}", op_name(*op), temp, lhs, rhs));
+                temp
+            },
+        }
+    }
+}
+
+fn op_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Mul => "mult",
+        BinOp::Div => "div",
+        BinOp::Mod => "mod",
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Less => "lt",
+        BinOp::LessEqual => "le",
+        BinOp::Greater => "gt",
+        BinOp::GreaterEqual => "ge",
+        BinOp::Equal => "eq",
+        BinOp::NotEqual => "neq",
+        BinOp::And | BinOp::Or => unreachable!("short-circuit operators are lowered with branches"),
+    }
+}
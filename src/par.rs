@@ -0,0 +1,850 @@
+use slice_deque::SliceDeque;
+use crate::ast::{BinOp, Case, Declarator, Expr, ExprKind, Function, Param, Place, Program, Stmt, StmtKind};
+use crate::lex::{Diag, Lex, Span, Tok};
+
+pub struct Par<'a> {
+    pub lex: Lex<'a>,
+    toks: SliceDeque<Tok<'a>>,
+    spans: SliceDeque<Span>,
+    last: Span, // span of the most recently consumed token
+    pub problems: Vec<Diag>,
+}
+
+impl<'a> Par<'a> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(source: &'a str) -> Par<'a> {
+        Par::new(Lex::from_str(source))
+    }
+
+    pub fn new(lex: Lex<'a>) -> Par<'a> {
+        Par{
+            lex, toks: SliceDeque::new(), spans: SliceDeque::new(), last: Span::default(), problems: Vec::new(),
+        }
+    }
+
+    fn tokens(&mut self, amt: usize) -> &mut [Tok<'a>] {
+        while self.toks.len() < amt {
+            let token = self.lex.next();
+            self.toks.push_back(token.tok);
+            self.spans.push_back(token.span);
+        }
+        &mut self.toks[0..amt]
+    }
+
+    fn consume(&mut self, amt: usize) {
+        for _ in 0..amt {
+            self.toks.pop_front();
+            if let Some(span) = self.spans.pop_front() { self.last = span; }
+        }
+    }
+
+    // from the start of `first` to the end of the last consumed token
+    fn span_from(&self, first: Span) -> Span {
+        Span { start: first.start, end: self.last.end }
+    }
+
+    // report a problem at the next token of lookahead, which is the one that didn't fit.
+    // An error token was already reported by the lexer, so don't pile a second message on it,
+    // and one token only gets one problem however many constructs it breaks.
+    fn error(&mut self, msg: &str) {
+        if let Tok::Error(_) = self.tokens(1)[0] { return; }
+        let span = self.spans[0];
+        if self.problems.last().is_some_and(|last| last.span.start == span.start) { return; }
+        self.problems.push(Diag { span, msg: msg.to_string() });
+    }
+
+    // skip ahead to somewhere parsing can pick up again after a problem: just past a `;` or a
+    // balanced `{ ... }` (and any `else { ... }` after it), or in front of a `}` or `func`
+    // that belongs to something enclosing the broken statement.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.tokens(1) {
+                &mut [Tok::Semicolon] if depth == 0 => { self.consume(1); return; },
+                &mut [Tok::RightCurly] if depth == 0 => return,
+                &mut [Tok::Func] | &mut [Tok::Empty] => return,
+                &mut [Tok::LeftCurly] => depth += 1,
+                &mut [Tok::RightCurly] => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.consume(1);
+                        if let Tok::Else = self.tokens(1)[0] { self.consume(1); continue; }
+                        return;
+                    }
+                },
+                _ => {},
+            }
+            self.consume(1);
+        }
+    }
+
+    /// Parse the whole input. Syntax errors are collected in `problems` and parsing picks up
+    /// after each one, so the program only holds the parts that made sense.
+    pub fn parse(&mut self) -> Program {
+        let mut globals = Vec::new();
+        let mut functions = Vec::new();
+        loop {
+            match self.tokens(1) {
+                &mut [Tok::Func] => {
+                    match self.function() {
+                        Some(function) => functions.push(function),
+                        None => self.skip_function(),
+                    }
+                },
+                &mut [Tok::Int] => {
+                    match self.statement() {
+                        Some(global) => globals.push(global),
+                        None => self.synchronize(),
+                    }
+                },
+                &mut [Tok::Const] => {
+                    match self.constant() {
+                        Some(global) => globals.push(global),
+                        None => self.synchronize(),
+                    }
+                },
+                &mut [Tok::Empty] => { return Program { globals, functions } },
+                _ => { self.expected("`func`, `int` or `const`"); self.skip_function(); },
+            }
+        }
+    }
+
+    // after a problem outside any function body, resume at the next `func`, or at an `int` or
+    // `const` that starts a global: one outside any braces, right after a `;` or `}`. An `int`
+    // after a `,` is still part of a broken parameter list.
+    fn skip_function(&mut self) {
+        self.consume(1);
+        let mut depth = 0usize;
+        let mut boundary = true;
+        loop {
+            match self.tokens(1) {
+                &mut [Tok::Func] | &mut [Tok::Empty] => return,
+                &mut [Tok::Int] | &mut [Tok::Const] if depth == 0 && boundary => return,
+                &mut [Tok::LeftCurly] => depth += 1,
+                &mut [Tok::RightCurly] => depth = depth.saturating_sub(1),
+                _ => {},
+            }
+            boundary = matches!(self.toks[0], Tok::Semicolon | Tok::RightCurly);
+            self.consume(1);
+        }
+    }
+
+    // `const N = value;`
+    fn constant(&mut self) -> Option<Stmt> {
+        self.tokens(1);
+        let first = self.spans[0];
+        let name = match self.tokens(3) {
+            &mut [Tok::Const, Tok::Identifier(ref mut id), Tok::Assign] => {
+                let id = std::mem::take(id);
+                self.consume(3);
+                String::from_utf8_lossy(id).to_string()
+            },
+            &mut [Tok::Const, Tok::Identifier(_), _] => {
+                self.consume(2);
+                self.expected("`=` after constant name");
+                return None
+            },
+            _ => {
+                self.consume(1);
+                self.expected("constant name after `const`");
+                return None
+            },
+        };
+        let value = self.expr()?;
+        self.expect(&Tok::Semicolon, "after constant")?;
+        Some(Stmt { kind: StmtKind::Const { name, value }, span: self.span_from(first) })
+    }
+
+    fn function(&mut self) -> Option<Function> {
+        self.tokens(3);
+        let first = self.spans[0];
+        let name = match self.tokens(3) {
+            &mut [Tok::Func, Tok::Identifier(ref mut id), Tok::LeftParen] => {
+                let name = std::mem::take(id);
+                self.consume(3);
+                name
+            },
+            &mut [Tok::Func, Tok::Identifier(_), _] => {
+                self.consume(2);
+                self.expected("`(` after function name");
+                return None;
+            },
+            _ => {
+                self.consume(1);
+                self.expected("function name after `func`");
+                return None;
+            }
+        };
+        let mut params: Vec<Param> = Vec::new();
+
+        loop  {
+            let array = match self.tokens(3) {
+                &mut [Tok::RightParen, _,_] => {
+                    self.consume(1);
+                    break;
+                }
+                &mut [Tok::Int, Tok::LeftBracket, Tok::RightBracket] => {
+                    self.consume(3);
+                    true
+                }
+                &mut [Tok::Int, Tok::LeftBracket, _] => {
+                    self.consume(2);
+                    self.expected("`]` after `int[`");
+                    return None
+                }
+                &mut [Tok::Int, _, _] => {
+                    self.consume(1);
+                    false
+                }
+                _=> {
+                    self.expected("`int` or `)` in parameter list");
+                    return None
+                }
+            };
+            match self.tokens(2) {
+                &mut [Tok::Identifier(ref mut id), Tok::Comma] => {
+                    let arg = std::mem::take(id);
+                    let span = self.spans[0];
+                    self.consume(2);
+                    params.push(Param { name: String::from_utf8_lossy(arg).to_string(), array, span });
+                },
+                &mut [Tok::Identifier(ref mut id), Tok::RightParen] => {
+                    let arg = std::mem::take(id);
+                    let span = self.spans[0];
+                    self.consume(2);
+                    params.push(Param { name: String::from_utf8_lossy(arg).to_string(), array, span });
+                    break
+                }
+                &mut [Tok::Identifier(_), _] => {
+                    self.consume(1);
+                    self.expected("`,` or `)` after parameter");
+                    return None
+                }
+                _ => {
+                    self.expected(if array { "parameter name after `int[]`" } else { "parameter name after `int`" });
+                    return None
+                }
+            }
+        }
+        let void = match self.tokens(2) {
+            &mut [Tok::Arrow, Tok::Int] => { self.consume(2); false },
+            &mut [Tok::Arrow, Tok::Void] => { self.consume(2); true },
+            &mut [Tok::Arrow, _] => {
+                self.consume(1);
+                self.expected("`int` or `void` after `->`");
+                return None
+            },
+            _ => false,
+        };
+        let body = self.statements()?;
+        Some(Function { name: String::from_utf8_lossy(name).to_string(), params, void, body, span: self.span_from(first) })
+    }
+
+    fn statements(&mut self) -> Option<Vec<Stmt>> {
+        self.expect(&Tok::LeftCurly, "to start a block")?;
+        let mut stmts = Vec::new();
+        loop {
+            match self.tokens(1) {
+                &mut [Tok::RightCurly] => {
+                    self.consume(1);
+                    break Some(stmts);
+                },
+                &mut [Tok::Func] | &mut [Tok::Empty] => {
+                    self.expected("`}` to close the block");
+                    break Some(stmts);
+                },
+                _ => {},
+            }
+
+            match self.statement() {
+                Some(stmt) => stmts.push(stmt),
+                None => self.synchronize(),
+            }
+        }
+
+    }
+
+    // `[4][4] grid;`, `[N * 2] buf;` or `[3] p = {2, 3, 5};` after the `int` of an array declaration
+    fn array_declaration(&mut self) -> Option<StmtKind> {
+        let mut dims = Vec::new();
+        while let &mut [Tok::LeftBracket] = self.tokens(1) {
+            self.consume(1);
+            dims.push(self.expr()?);
+            self.expect(&Tok::RightBracket, "after array size")?;
+        }
+        let name = match self.tokens(1) {
+            &mut [Tok::Identifier(ref mut id)] => {
+                let id = std::mem::take(id);
+                self.consume(1);
+                String::from_utf8_lossy(id).to_string()
+            },
+            _ => { self.expected("array name"); return None },
+        };
+        match self.tokens(1) {
+            &mut [Tok::Semicolon] => {
+                self.consume(1);
+                Some(StmtKind::DeclareArray { name, dims, init: None })
+            },
+            &mut [Tok::Assign] => {
+                self.consume(1);
+                self.expect(&Tok::LeftCurly, "to start the array initializer")?;
+                let Some(values) = self.initializer() else {
+                    // the initializer's own `}` would otherwise look like the end of the block
+                    while !matches!(self.tokens(1)[0], Tok::RightCurly | Tok::Semicolon | Tok::Func | Tok::Empty) {
+                        self.consume(1);
+                    }
+                    if let Tok::RightCurly = self.toks[0] { self.consume(1); }
+                    return None
+                };
+                self.expect(&Tok::Semicolon, "after array declaration")?;
+                Some(StmtKind::DeclareArray { name, dims, init: Some(values) })
+            },
+            _ => { self.expected("`;` or `=` after array name"); None },
+        }
+    }
+
+    // any number of `[expr]` after an array name
+    fn indices(&mut self) -> Option<Vec<Expr>> {
+        let mut indices = Vec::new();
+        while let &mut [Tok::LeftBracket] = self.tokens(1) {
+            self.consume(1);
+            indices.push(self.expr()?);
+            self.expect(&Tok::RightBracket, "after array index")?;
+        }
+        Some(indices)
+    }
+
+    // `a, b, c}` after the `{` of an array initializer
+    fn initializer(&mut self) -> Option<Vec<Expr>> {
+        let mut values = Vec::new();
+        if let &mut [Tok::RightCurly] = self.tokens(1) {
+            self.consume(1);
+            return Some(values);
+        }
+        loop {
+            values.push(self.expr()?);
+            match self.tokens(1) {
+                &mut [Tok::Comma] => self.consume(1),
+                &mut [Tok::RightCurly] => { self.consume(1); return Some(values) },
+                _ => { self.expected("`,` or `}` in array initializer"); return None },
+            }
+        }
+    }
+
+    // `a, b = 2, c;` after the `int` of a declaration
+    fn declarators(&mut self) -> Option<Vec<Declarator>> {
+        let mut vars = Vec::new();
+        loop {
+            self.tokens(1);
+            let first = self.spans[0];
+            let name = match self.tokens(2) {
+                &mut [Tok::Identifier(ref mut id), _] => {
+                    let id = std::mem::take(id);
+                    self.consume(1);
+                    String::from_utf8_lossy(id).to_string()
+                },
+                _ => { self.expected("variable name"); return None },
+            };
+            let value = match self.tokens(1) {
+                &mut [Tok::Assign] => {
+                    self.consume(1);
+                    Some(self.expr()?)
+                },
+                _ => None,
+            };
+            vars.push(Declarator { name, value, span: self.span_from(first) });
+            match self.tokens(1) {
+                &mut [Tok::Comma] => self.consume(1),
+                &mut [Tok::Semicolon] => { self.consume(1); return Some(vars) },
+                _ if vars.last().unwrap().value.is_some() => { self.expected("`,` or `;` after declaration"); return None },
+                _ => { self.expected("`=`, `,` or `;` after variable name"); return None },
+            }
+        }
+    }
+
+    // the labels and statements of a switch body, after its `{`
+    fn cases(&mut self) -> Vec<Case> {
+        let mut cases: Vec<Case> = Vec::new();
+        loop {
+            self.tokens(1);
+            let first = self.spans[0];
+            let value = match self.tokens(3) {
+                &mut [Tok::RightCurly, _, _] => {
+                    self.consume(1);
+                    return cases;
+                },
+                &mut [Tok::Func, _, _] | &mut [Tok::Empty, _, _] => {
+                    self.expected("`}` to close the switch");
+                    return cases;
+                },
+                &mut [Tok::Case, _, _] => {
+                    self.consume(1);
+                    self.expr().map(Some)
+                },
+                &mut [Tok::Default, _, _] => {
+                    self.consume(1);
+                    if cases.iter().any(|case| case.value.is_none()) {
+                        self.problems.push(Diag { span: first, msg: "a switch can only have one `default`".to_string() });
+                    }
+                    Some(None)
+                },
+                _ => {
+                    if cases.is_empty() {
+                        self.expected("`case` or `default`");
+                        self.synchronize();
+                    } else {
+                        match self.statement() {
+                            Some(stmt) => cases.last_mut().unwrap().body.push(stmt),
+                            None => self.synchronize(),
+                        }
+                    }
+                    continue;
+                },
+            };
+            let Some(value) = value else { self.synchronize(); continue };
+            if self.expect(&Tok::Colon, "after case label").is_none() {
+                self.synchronize();
+                continue;
+            }
+            cases.push(Case { value, body: Vec::new(), span: self.span_from(first) });
+        }
+    }
+
+    // report that `what` should have come next instead of the token that did
+    fn expected(&mut self, what: &str) {
+        self.tokens(1);
+        let found = match self.toks[0] {
+            Tok::Empty => "end of input".to_string(),
+            _ => format!("`{}`", String::from_utf8_lossy(self.lex.source(self.spans[0]))),
+        };
+        self.error(&format!("expected {}, found {}", what, found));
+    }
+
+    // consume `want`, which has to come next
+    fn expect(&mut self, want: &Tok, context: &str) -> Option<()> {
+        if std::mem::discriminant(&self.tokens(1)[0]) == std::mem::discriminant(want) {
+            self.consume(1);
+            Some(())
+        } else {
+            self.expected(&format!("`{}` {}", want, context));
+            None
+        }
+    }
+
+    fn statement(&mut self) -> Option<Stmt> {
+        self.tokens(8);
+        let first = self.spans[0];
+        let kind = match self.tokens(8) {
+            &mut[Tok::Int, Tok::LeftBracket,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.array_declaration()?
+            },
+            &mut[Tok::Int, Tok::Identifier(_),_,_,_,_,_,_] => {
+                self.consume(1);
+                StmtKind::Declare(self.declarators()?)
+            },
+            &mut[Tok::Int, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.expected("variable name or `[` after `int`");
+                return None
+            },
+            &mut[Tok::Const, _,_,_,_,_,_,_] => return self.constant(),
+
+            &mut[Tok::Identifier(ref mut id), Tok::Assign,_,_,_,_,_,_] => {
+                let id = std::mem::take(id);
+                self.consume(2);
+                let place = Place { name: String::from_utf8_lossy(id).to_string(), indices: Vec::new(), span: first };
+                let value = self.expr()?;
+                self.expect(&Tok::Semicolon, "after assignment")?;
+                StmtKind::Assign { place, value }
+            },
+
+            // anything else that starts like an expression: `arr[i] = x;` or a bare `log(x);`
+            &mut[Tok::Identifier(_),_,_,_,_,_,_,_] | &mut[Tok::Number(_),_,_,_,_,_,_,_]
+            | &mut[Tok::LeftParen,_,_,_,_,_,_,_] | &mut[Tok::Not,_,_,_,_,_,_,_]
+            | &mut[Tok::Subtract,_,_,_,_,_,_,_] | &mut[Tok::Plus,_,_,_,_,_,_,_] => {
+                self.simple(&Tok::Semicolon)?
+            },
+            &mut[Tok::Increment,_,_,_,_,_,_,_] | &mut[Tok::Decrement,_,_,_,_,_,_,_] => {
+                self.simple(&Tok::Semicolon)?
+            },
+            &mut[Tok::For, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.expect(&Tok::LeftParen, "after `for`")?;
+                self.tokens(1);
+                let init_start = self.spans[0];
+                let init = match self.tokens(1) {
+                    &mut [Tok::Semicolon] => { self.consume(1); None },
+                    &mut [Tok::Int] => Some(Box::new(self.statement()?)),
+                    _ => {
+                        let kind = self.simple(&Tok::Semicolon)?;
+                        Some(Box::new(Stmt { kind, span: self.span_from(init_start) }))
+                    },
+                };
+                let cond = match self.tokens(1) {
+                    &mut [Tok::Semicolon] => None,
+                    _ => Some(self.expr()?),
+                };
+                self.expect(&Tok::Semicolon, "after `for` condition")?;
+                self.tokens(1);
+                let step_start = self.spans[0];
+                let step = match self.tokens(1) {
+                    &mut [Tok::RightParen] => { self.consume(1); None },
+                    _ => {
+                        let kind = self.simple(&Tok::RightParen)?;
+                        Some(Box::new(Stmt { kind, span: self.span_from(step_start) }))
+                    },
+                };
+                let body = self.statements()?;
+                StmtKind::For { init, cond, step, body }
+            },
+            &mut[Tok::While, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                let cond = self.expr()?;
+                let body = self.statements()?;
+                StmtKind::While { cond, body }
+            },
+            &mut[Tok::If, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                let cond = self.expr()?;
+                let then = self.statements()?;
+                let otherwise = match self.tokens(2) {
+                    &mut [Tok::Else, Tok::If] => {
+                        self.consume(1);
+                        Some(vec![self.statement()?])
+                    },
+                    &mut [Tok::Else, _] => {
+                        self.consume(1);
+                        Some(self.statements()?)
+                    },
+                    _ => None,
+                };
+                StmtKind::If { cond, then, otherwise }
+            },
+            &mut[Tok::Switch, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                let value = self.expr()?;
+                self.expect(&Tok::LeftCurly, "after `switch` value")?;
+                StmtKind::Switch { value, cases: self.cases() }
+            },
+            &mut[Tok::Print, Tok::LeftParen, Tok::Str(ref mut text), _,_,_,_,_] => {
+                let text = std::mem::take(text);
+                self.consume(3);
+                self.expect(&Tok::RightParen, "after string")?;
+                self.expect(&Tok::Semicolon, "after `print(...)`")?;
+                StmtKind::PrintStr(text.into_owned())
+            },
+            &mut[Tok::Print, Tok::LeftParen,_,_,_,_,_,_] => {
+                self.consume(2);
+                let value = self.expr()?;
+                self.expect(&Tok::RightParen, "after print argument")?;
+                self.expect(&Tok::Semicolon, "after `print(...)`")?;
+                StmtKind::Print(value)
+            },
+            &mut[Tok::Read, Tok::LeftParen, Tok::Identifier(ref mut id), Tok::LeftBracket,_,_,_,_] => {
+                let id = std::mem::take(id);
+                let span = self.spans[2];
+                self.consume(3);
+                let indices = self.indices()?;
+                let place = Place { name: String::from_utf8_lossy(id).to_string(), indices, span: self.span_from(span) };
+                self.expect(&Tok::RightParen, "after read target")?;
+                self.expect(&Tok::Semicolon, "after `read(...)`")?;
+                StmtKind::Read(place)
+            },
+            &mut[Tok::Read, Tok::LeftParen, Tok::Identifier(ref mut id), _,_,_,_,_] => {
+                let id = std::mem::take(id);
+                let span = self.spans[2];
+                self.consume(3);
+                self.expect(&Tok::RightParen, "after read target")?;
+                self.expect(&Tok::Semicolon, "after `read(...)`")?;
+                StmtKind::Read(Place { name: String::from_utf8_lossy(id).to_string(), indices: Vec::new(), span })
+            },
+            &mut[Tok::Read, Tok::LeftParen, _,_,_,_,_,_] => {
+                self.consume(2);
+                self.expected("variable name to read into");
+                return None
+            },
+            &mut[Tok::Print, _,_,_,_,_,_,_] | &mut[Tok::Read, _,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.expected("`(`");
+                return None
+            },
+            &mut[Tok::Return, Tok::Semicolon,_,_,_,_,_,_] => {
+                self.consume(2);
+                StmtKind::Return(None)
+            },
+            &mut[Tok::Return,_,_,_,_,_,_,_] => {
+                self.consume(1);
+                let value = self.expr()?;
+                self.expect(&Tok::Semicolon, "after return value")?;
+                StmtKind::Return(Some(value))
+            },
+            &mut[Tok::Break,_,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.expect(&Tok::Semicolon, "after `break`")?;
+                StmtKind::Break
+            },
+            &mut[Tok::Continue,_,_,_,_,_,_,_] => {
+                self.consume(1);
+                self.expect(&Tok::Semicolon, "after `continue`")?;
+                StmtKind::Continue
+            }
+            _ => {self.expected("a statement"); return None}
+        };
+        Some(Stmt { kind, span: self.span_from(first) })
+    }
+
+    // the left side of an `=`, which has to be something that can be stored to
+    fn place(&mut self, target: Expr, what: &str) -> Option<Place> {
+        match target.kind {
+            ExprKind::Var(name) => Some(Place { name, indices: Vec::new(), span: target.span }),
+            ExprKind::Index(name, indices) => Some(Place { name, indices, span: target.span }),
+            _ => {
+                self.problems.push(Diag { span: target.span, msg: format!("expected a variable or array element {}", what) });
+                None
+            }
+        }
+    }
+
+    // an assignment, update or bare expression, finished by `end`: `;` in a statement, `)` as a `for` step
+    fn simple(&mut self, end: &Tok) -> Option<StmtKind> {
+        match self.tokens(1) {
+            &mut [Tok::Increment] | &mut [Tok::Decrement] => {
+                let op = if let Tok::Increment = self.toks[0] { BinOp::Add } else { BinOp::Sub };
+                let one = Expr { kind: ExprKind::Number(1), span: self.spans[0] };
+                let what = format!("after `{}`", self.toks[0]);
+                self.consume(1);
+                let target = self.expr()?;
+                let place = self.place(target, &what)?;
+                self.expect(end, "after increment")?;
+                Some(StmtKind::Update { place, op, value: one })
+            },
+            _ => {
+                let target = self.expr()?;
+                self.assignment(target, end)
+            },
+        }
+    }
+
+    // what follows the expression of a simple statement: `= x`, `+= x`, `++` or nothing, and then `end`
+    fn assignment(&mut self, target: Expr, end: &Tok) -> Option<StmtKind> {
+        self.tokens(1);
+        let what = format!("before `{}`", self.toks[0]);
+        let op = match self.tokens(1) {
+            &mut [Tok::Assign] => None,
+            &mut [Tok::PlusAssign] => Some(BinOp::Add),
+            &mut [Tok::SubtractAssign] => Some(BinOp::Sub),
+            &mut [Tok::MultiplyAssign] => Some(BinOp::Mul),
+            &mut [Tok::DivideAssign] => Some(BinOp::Div),
+            &mut [Tok::ModulusAssign] => Some(BinOp::Mod),
+            &mut [Tok::Increment] | &mut [Tok::Decrement] => {
+                let op = if let Tok::Increment = self.toks[0] { BinOp::Add } else { BinOp::Sub };
+                let one = Expr { kind: ExprKind::Number(1), span: self.spans[0] };
+                let place = self.place(target, &what)?;
+                self.consume(1);
+                self.expect(end, "after increment")?;
+                return Some(StmtKind::Update { place, op, value: one });
+            },
+            _ => {
+                self.expect(end, "after expression")?;
+                return Some(StmtKind::Expr(target));
+            },
+        };
+        let place = self.place(target, &what)?;
+        self.consume(1);
+        let value = self.expr()?;
+        self.expect(end, "after assignment")?;
+        match op {
+            Some(op) => Some(StmtKind::Update { place, op, value }),
+            None => Some(StmtKind::Assign { place, value }),
+        }
+    }
+
+    fn binary(op: BinOp, lhs: Expr, rhs: Expr) -> Expr {
+        let span = Span { start: lhs.span.start, end: rhs.span.end };
+        Expr { kind: ExprKind::Binary(op, Box::new(lhs), Box::new(rhs)), span }
+    }
+
+    fn mul_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.unary_expr()?;
+        loop {
+            let op = match self.tokens(1) {
+                &mut [Tok::Multiply] => BinOp::Mul,
+                &mut [Tok::Divide] => BinOp::Div,
+                &mut [Tok::Modulus] => BinOp::Mod,
+                _ => break,
+            };
+            self.consume(1);
+            let rhs = self.unary_expr()?;
+            lhs = Par::binary(op, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn add_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.mul_expr()?;
+        loop {
+            let op = match self.tokens(1) {
+                &mut [Tok::Plus] => BinOp::Add,
+                &mut [Tok::Subtract] => BinOp::Sub,
+                _ => break,
+            };
+            self.consume(1);
+            let rhs = self.mul_expr()?;
+            lhs = Par::binary(op, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn bool_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.add_expr()?;
+        loop {
+            let op = match self.tokens(1) {
+                &mut [Tok::Less] => BinOp::Less,
+                &mut [Tok::Greater] => BinOp::Greater,
+                &mut [Tok::GreaterEqual] => BinOp::GreaterEqual,
+                &mut [Tok::LessEqual] => BinOp::LessEqual,
+                _ => break,
+            };
+            self.consume(1);
+            let rhs = self.add_expr()?;
+            lhs = Par::binary(op, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn unary_expr(&mut self) -> Option<Expr> {
+        match self.tokens(2) {
+            &mut [Tok::Not, _] => {
+                let first = self.spans[0];
+                self.consume(1);
+                let operand = self.unary_expr()?;
+                Some(Expr { kind: ExprKind::Not(Box::new(operand)), span: self.span_from(first) })
+            }
+            // a literal is negated right away, which is the only way to write -2147483648
+            &mut [Tok::Subtract, Tok::Number(num)] => {
+                let first = self.spans[0];
+                self.consume(2);
+                Some(Expr { kind: ExprKind::Number(-(num as i64) as i32), span: self.span_from(first) })
+            }
+            &mut [Tok::Subtract, _] => {
+                let first = self.spans[0];
+                self.consume(1);
+                let operand = self.unary_expr()?;
+                Some(Expr { kind: ExprKind::Neg(Box::new(operand)), span: self.span_from(first) })
+            }
+            &mut [Tok::Plus, _] => {
+                let first = self.spans[0];
+                self.consume(1);
+                let operand = self.unary_expr()?;
+                Some(Expr { kind: operand.kind, span: self.span_from(first) })
+            }
+            _ => self.base_expr(),
+        }
+    }
+
+    fn expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.and_expr()?;
+        while let &mut [Tok::Or] = self.tokens(1) {
+            self.consume(1);
+            let rhs = self.and_expr()?;
+            lhs = Par::binary(BinOp::Or, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn and_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.eq_expr()?;
+        while let &mut [Tok::And] = self.tokens(1) {
+            self.consume(1);
+            let rhs = self.eq_expr()?;
+            lhs = Par::binary(BinOp::And, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn eq_expr(&mut self) -> Option<Expr> {
+        let mut lhs = self.bool_expr()?;
+        loop {
+            let op = match self.tokens(1) {
+                &mut [Tok::Equality] => BinOp::Equal,
+                &mut [Tok::NotEqual] => BinOp::NotEqual,
+                _ => break,
+            };
+            self.consume(1);
+            let rhs = self.bool_expr()?;
+            lhs = Par::binary(op, lhs, rhs);
+        }
+        Some(lhs)
+    }
+
+    fn base_expr(&mut self) -> Option<Expr> {
+        self.tokens(1);
+        let first = self.spans[0];
+        let kind = match self.tokens(1) {
+            &mut [Tok::Identifier(id)] => {
+                self.consume(1);
+                let id = String::from_utf8_lossy(id).to_string();
+
+                // Handle array indexing: ID [ expr ] [ expr ] ...
+                if let &mut [Tok::LeftBracket] = self.tokens(1) {
+                    ExprKind::Index(id, self.indices()?)
+                }
+                // Handle function calls: ID ( args )
+                else if let &mut [Tok::LeftParen] = self.tokens(1) {
+                    self.consume(1);
+                    let arguments = self.args()?;
+                    if let &mut [Tok::RightParen] = self.tokens(1) {
+                        self.consume(1);
+                    } else {
+                        self.expected("`,` or `)` after function argument");
+                        return None;
+                    }
+                    ExprKind::Call(id, arguments)
+                } else {
+                    // Standalone identifier
+                    ExprKind::Var(id)
+                }
+            }
+            &mut [Tok::Number(num)] => {
+                let Ok(num) = i32::try_from(num) else {
+                    let text = String::from_utf8_lossy(self.lex.source(self.spans[0])).to_string();
+                    self.error(&format!("integer literal {} does not fit in a 32-bit int, only its negation does", text));
+                    self.consume(1);
+                    return None;
+                };
+                self.consume(1);
+                ExprKind::Number(num)
+            }
+            &mut [Tok::LeftParen] => {
+                self.consume(1);
+                let expr = self.expr()?;
+                if let &mut [Tok::RightParen] = self.tokens(1) {
+                    self.consume(1);
+                } else {
+                    self.expected("`)` to close `(`");
+                    return None;
+                }
+                return Some(expr);
+            }
+            _ => {
+                self.expected("an expression");
+                return None;
+            }
+        };
+        Some(Expr { kind, span: self.span_from(first) })
+    }
+
+    fn args(&mut self) -> Option<Vec<Expr>> {
+        let mut arguments = Vec::new();
+        if let &mut [Tok::RightParen] = self.tokens(1) {
+            return Some(arguments);
+        }
+        arguments.push(self.expr()?);
+        while let &mut [Tok::Comma] = self.tokens(1) {
+            self.consume(1);
+            arguments.push(self.expr()?);
+        }
+        Some(arguments)
+    }
+}
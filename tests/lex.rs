@@ -0,0 +1,110 @@
+use lab1::{Lex, Tok};
+
+// lex all of `source`, keeping every token's spelling and the problems found along the way
+fn lex(source: &str) -> (Vec<String>, Lex<'_>) {
+    let mut lex = Lex::from_str(source);
+    let mut toks = Vec::new();
+    loop {
+        let token = lex.next();
+        if let Tok::Empty = token.tok { break; }
+        toks.push(token.tok.to_string());
+    }
+    (toks, lex)
+}
+
+#[test]
+fn every_stray_character_is_its_own_problem() {
+    let (toks, lex) = lex("a $ b $$\n");
+    assert_eq!(toks, vec!["a", "$", "b", "$", "$"]);
+    assert_eq!(lex.problems.len(), 3);
+    let spans: Vec<(usize, usize, usize)> = lex.problems.iter()
+        .map(|err| (err.span.start.line, err.span.start.col, err.span.end.col))
+        .collect();
+    assert_eq!(spans, vec![(1, 3, 4), (1, 7, 8), (1, 8, 9)]);
+    assert!(lex.problems.iter().all(|err| err.msg == "Lexer: found invalid char $"));
+}
+
+#[test]
+fn nested_block_comments_keep_line_numbers() {
+    let mut lex = Lex::from_str("a /* one\n /* two\n */ still\n */ b\n");
+    assert!(matches!(lex.next().tok, Tok::Identifier(b"a")));
+    let b = lex.next();
+    assert!(matches!(b.tok, Tok::Identifier(b"b")));
+    assert_eq!((b.span.start.line, b.span.start.col), (4, 5));
+    assert!(lex.problems.is_empty());
+}
+
+#[test]
+fn unterminated_block_comment_points_at_its_opening() {
+    let (toks, lex) = lex("a\n  /* open /* nested */\nb");
+    assert_eq!(toks, vec!["a"]);
+    assert_eq!(lex.problems.len(), 1);
+    let err = &lex.problems[0];
+    assert_eq!(err.msg, "Lexer: unterminated block comment");
+    assert_eq!((err.span.start.line, err.span.start.col, err.span.end.col), (2, 3, 5));
+}
+
+#[test]
+fn number_literals_in_every_base() {
+    let mut lex = Lex::from_str("0x1F 0XfF 0b1010 0o17 1_000_000 0b1111_0000 2147483647 2147483648");
+    let mut values = Vec::new();
+    loop {
+        match lex.next().tok {
+            Tok::Number(num) => values.push(num),
+            Tok::Empty => break,
+            tok => panic!("not a number: {}", tok),
+        }
+    }
+    assert_eq!(values, vec![31, 255, 10, 15, 1_000_000, 240, 2147483647, 2147483648]);
+    assert!(lex.problems.is_empty());
+}
+
+#[test]
+fn bad_number_literals_are_reported() {
+    let (toks, lex) = lex("0x 12ab 0b102 0o8 2147483649 4294967296 2_ 0x_1");
+    assert_eq!(toks.len(), 8);
+    let msgs: Vec<&str> = lex.problems.iter().map(|err| err.msg.as_str()).collect();
+    assert_eq!(msgs, vec![
+        "Lexer: hexadecimal integer literal 0x has no digits",
+        "Lexer: integer literal 12ab has an invalid digit",
+        "Lexer: binary integer literal 0b102 has an invalid digit",
+        "Lexer: octal integer literal 0o8 has an invalid digit",
+        "Lexer: integer literal 2147483649 does not fit in a 32-bit int",
+        "Lexer: integer literal 4294967296 does not fit in a 32-bit int",
+        "Lexer: integer literal 2_ has a '_' that doesn't separate digits",
+        "Lexer: hexadecimal integer literal 0x_1 has a '_' that doesn't separate digits",
+    ]);
+}
+
+#[test]
+fn ir_gets_the_decimal_value() {
+    let mut par = lab1::Par::from_str("func main() {\n    print(0x1F + 0b11 * 1_000);\n}\n");
+    let program = par.parse();
+    assert!(par.problems.is_empty());
+    let ir = lab1::lower(&program).ok().unwrap();
+    assert!(ir.contains("%mult temp0, 3, 1000\n%int temp1\n%add temp1, 31, temp0\n"));
+}
+
+#[test]
+fn string_escapes_are_decoded() {
+    let mut lex = Lex::from_str(r#""a\n\t\"\\\x41" "plain""#);
+    let Tok::Str(text) = lex.next().tok else { panic!("not a string") };
+    assert_eq!(&text[..], b"a\n\t\"\\A");
+    let Tok::Str(text) = lex.next().tok else { panic!("not a string") };
+    assert_eq!(&text[..], b"plain");
+    assert!(lex.problems.is_empty());
+}
+
+#[test]
+fn bad_strings_are_reported() {
+    let (toks, lex) = lex("\"bad \\q\" \"\\xZ1\" \"open\nx");
+    assert_eq!(toks.len(), 4);
+    let problems: Vec<(usize, usize, &str)> = lex.problems.iter()
+        .map(|err| (err.span.start.line, err.span.start.col, err.msg.as_str()))
+        .collect();
+    assert_eq!(problems, vec![
+        (1, 1, "Lexer: invalid escape \\q in string \"bad \\q\""),
+        (1, 10, "Lexer: invalid escape \\x in string \"\\xZ1\""),
+        (1, 17, "Lexer: unterminated string \"open"),
+    ]);
+}
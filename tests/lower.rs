@@ -0,0 +1,341 @@
+use lab1::{lower, Par};
+
+// the IR for `source`, or the message of every problem lowering found
+fn ir(source: &str) -> Result<String, Vec<String>> {
+    let mut par = Par::from_str(source);
+    let program = par.parse();
+    assert!(par.problems.is_empty(), "syntax errors in test program");
+    lower(&program).map_err(|errs| errs.into_iter().map(|err| err.msg).collect())
+}
+
+#[test]
+fn shadowing_loop_variable_gets_its_own_storage() {
+    let ir = ir("func main() {
+    int i = 5;
+    for (int i = 0; i < 2; i++) {}
+    for (int i = 0; i < 3; i++) {}
+    print(i);
+}
+").unwrap();
+    assert!(ir.contains("%int i\n%mov i, 5\n"));
+    assert!(ir.contains("%int i_1\n%mov i_1, 0\n"));
+    assert!(ir.contains("%int i_2\n%mov i_2, 0\n"));
+    assert!(ir.contains("%out i\n"));
+}
+
+#[test]
+fn local_shadowing_a_global_gets_its_own_storage() {
+    let ir = ir("int g;
+func main() {
+    int g = 1;
+    print(g);
+}
+func f(int g) {
+    return g;
+}
+").unwrap();
+    assert!(ir.starts_with("%int g\n"));
+    assert!(ir.contains("%int g_1\n%mov g_1, 1\n%out g_1\n"));
+    assert!(ir.contains("%func f(%int g_1)\n%ret g_1\n"));
+}
+
+#[test]
+fn indexing_an_undeclared_array_is_reported() {
+    let errs = ir("func main() {\n    nums[0] = 1;\n    print(nums[1]);\n}\n").unwrap_err();
+    assert_eq!(errs, vec!["array nums is not declared", "array nums is not declared"]);
+}
+
+#[test]
+fn calls_are_checked_against_every_function() {
+    let errs = ir("func main() {\n    int a;\n    a();\n    missing();\n    later(1, 2);\n}\nfunc later(int x) {\n    return x;\n}\n").unwrap_err();
+    assert_eq!(errs, vec![
+        "a is not a function",
+        "call to undefined function missing",
+        "later takes 1 arguments but is called with 2",
+    ]);
+}
+
+#[test]
+fn redeclaring_in_the_same_scope_is_reported() {
+    let errs = ir("const N = 2;
+func main() {
+    const N = 2;
+    int N;
+    const M = 1;
+    const M = 5;
+    if 1 {
+        int M = 4;
+        print(M);
+    }
+}
+").unwrap_err();
+    assert_eq!(errs, vec!["N is already declared", "M is already declared"]);
+}
+
+#[test]
+fn or_and_and_skip_their_right_side() {
+    let ir = ir("func main() {
+    int a;
+    int b;
+    print(a < 1 || b);
+    print(a && b > 2);
+}
+").unwrap();
+    // the right side is only computed after the branch that can jump past it
+    assert!(ir.contains("\
+%int temp1
+%mov temp1, 1
+%branch_if temp0, :label0
+%neq temp1, b, 0
+:label0
+%out temp1
+"));
+    assert!(ir.contains("\
+%int temp2
+%mov temp2, 0
+%int temp3
+%eq temp3, a, 0
+%branch_if temp3, :label1
+%int temp4
+%gt temp4, b, 2
+%neq temp2, temp4, 0
+:label1
+%out temp2
+"));
+}
+
+#[test]
+fn strings_are_printed_with_outs() {
+    let ir = ir("func main() {\n    print(\"tab\\t \\\"q\\\" \\\\ \\x41\\x01\\n\");\n}\n").unwrap();
+    assert_eq!(ir, "%func main()\n%outs \"tab\\t \\\"q\\\" \\\\ A\\x01\\n\"\n%endfunc\n");
+}
+
+#[test]
+fn switch_falls_through_until_break() {
+    let ir = ir("func main() {
+    int x;
+    switch x {
+        case 1:
+            print(1);
+        case -2:
+            print(2);
+            break;
+        default:
+            print(3);
+    }
+}
+").unwrap();
+    assert_eq!(ir, "\
+%func main()
+%int x
+%int temp0
+%eq temp0, x, 1
+%branch_if temp0, :label0
+%int temp1
+%eq temp1, x, -2
+%branch_if temp1, :label1
+%jmp :label2
+:label0
+%out 1
+:label1
+%out 2
+%jmp :label3
+:label2
+%out 3
+:label3
+%endfunc
+");
+}
+
+#[test]
+fn duplicate_cases_are_reported() {
+    let errs = ir("func main() {
+    int x;
+    switch x {
+        case 1:
+            break;
+        case 2:
+        case 1:
+            break;
+    }
+}
+").unwrap_err();
+    assert_eq!(errs, vec!["duplicate case 1"]);
+}
+
+#[test]
+fn declaration_lists_and_initializers() {
+    let ir = ir("func main() {\n    int a, b = 2, c;\n    int[3] p = {2, 3, b};\n}\n").unwrap();
+    assert_eq!(ir, "\
+%func main()
+%int a
+%int b
+%mov b, 2
+%int c
+%int[] p, 3
+%mov [p+0], 2
+%mov [p+1], 3
+%mov [p+2], b
+%endfunc
+");
+}
+
+#[test]
+fn initializer_length_is_checked() {
+    let errs = ir("func main() {\n    int[3] p = {2, 3};\n    int[1] q = {1, 2};\n}\n").unwrap_err();
+    assert_eq!(errs, vec![
+        "array p has 3 elements but 2 initial values",
+        "array q has 1 elements but 2 initial values",
+    ]);
+}
+
+#[test]
+fn elements_are_laid_out_row_by_row() {
+    let ir = ir("func main() {\n    int i;\n    int[2][3] grid;\n    grid[1][i] = 5;\n}\n").unwrap();
+    assert!(ir.contains("\
+%int[] grid, 6
+%int temp0
+%mult temp0, 1, 3
+%int temp1
+%add temp1, temp0, i
+%mov [grid+temp1], 5
+"));
+}
+
+#[test]
+fn dimension_count_is_checked() {
+    let errs = ir("func main() {
+    int[2][3] grid;
+    int[3] p;
+    int x;
+    grid[1] = 0;
+    print(p[0][1]);
+    print(x[0]);
+}
+").unwrap_err();
+    assert_eq!(errs, vec![
+        "grid has 2 dimensions but is indexed with 1",
+        "p has 1 dimension but is indexed with 2",
+        "x is not an array",
+    ]);
+}
+
+#[test]
+fn arrays_are_passed_by_name() {
+    let ir = ir("func first(int[] values) {\n    return values[0];\n}\nfunc main() {\n    int[4] v;\n    print(first(v));\n}\n").unwrap();
+    assert!(ir.starts_with("%func first(%int[] values)\n"));
+    assert!(ir.contains("%call temp1, first(v, )\n"));
+}
+
+#[test]
+fn array_and_int_arguments_are_checked() {
+    let errs = ir("func sum(int[] values, int n) {
+    return values[n];
+}
+func main() {
+    int[4] v;
+    int[2][2] m;
+    int x;
+    print(sum(v, 4));
+    print(sum(x, 1));
+    print(sum(m, 1));
+    print(sum(v, v));
+    print(v);
+}
+").unwrap_err();
+    assert_eq!(errs, vec![
+        "argument 1 of sum has to be a one-dimensional array",
+        "argument 1 of sum has to be a one-dimensional array",
+        "argument 2 of sum is an int, but v is an array",
+        "v is an array, so it needs an index here",
+    ]);
+}
+
+#[test]
+fn void_functions_return_at_their_end() {
+    let ir = ir("func log(int x) -> void {\n    print(x);\n}\nfunc main() -> int {\n    log(1);\n    return 0;\n}\n").unwrap();
+    assert!(ir.starts_with("%func log(%int x)\n%out x\n%ret\n%endfunc\n"));
+}
+
+#[test]
+fn void_results_and_returns_are_checked() {
+    let errs = ir("func log(int x) -> void {
+    print(x);
+    return x;
+}
+func main() {
+    int a = log(1);
+    log(2);
+    return;
+}
+").unwrap_err();
+    assert_eq!(errs, vec![
+        "log returns void, so `return` can't have a value",
+        "log returns void, so its result can't be used",
+        "main returns int, so `return` needs a value",
+    ]);
+}
+
+#[test]
+fn constants_cant_be_changed() {
+    let errs = ir("const N = 2;
+func main() {
+    const M = N + 1;
+    N = 3;
+    M += 1;
+    N++;
+    read(M);
+}
+").unwrap_err();
+    assert_eq!(errs, vec![
+        "N is a constant, so it can't be changed",
+        "M is a constant, so it can't be changed",
+        "N is a constant, so it can't be changed",
+        "M is a constant, so it can't be changed",
+    ]);
+}
+
+#[test]
+fn constant_expressions_are_folded() {
+    let ir = ir("const N = 4;
+const SIZE = N * N + 1;
+int[SIZE] buf;
+func main() {
+    const HALF = N / 2;
+    int[N][HALF] grid;
+    int x;
+    switch x {
+        case N - 1:
+            print(SIZE % 5);
+    }
+}
+").unwrap();
+    assert!(ir.starts_with("%int[] buf, 17\n"));
+    assert!(ir.contains("%int[] grid, 8\n"));
+    assert!(ir.contains("%eq temp0, x, 3\n"));
+    assert!(ir.contains("%mod temp1, 17, 5\n"));
+}
+
+#[test]
+fn constant_expressions_have_to_be_known() {
+    let errs = ir("const N = 2;
+func main() {
+    int x;
+    int[N - 2] a;
+    int[x] b;
+    const M = N / (N - 2);
+    const BIG = 2147483647 + N;
+    switch x {
+        case x:
+            break;
+    }
+}
+").unwrap_err();
+    assert_eq!(errs, vec![
+        "array a needs a positive size, not 0",
+        "the size of array b has to be known while compiling, using only numbers and constants",
+        "division by zero in constant expression",
+        "constant expression overflows",
+        "a case label has to be known while compiling, using only numbers and constants",
+    ]);
+}
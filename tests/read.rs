@@ -0,0 +1,14 @@
+use lab1::{read_source, Lex, Tok};
+
+#[test]
+fn any_reader_can_be_lexed() {
+    let src = read_source(std::io::Cursor::new(b"print(42);".to_vec())).unwrap();
+    let mut lex = Lex::new(&src);
+    let mut toks = Vec::new();
+    loop {
+        let token = lex.next();
+        if let Tok::Empty = token.tok { break; }
+        toks.push(token.tok.to_string());
+    }
+    assert_eq!(toks, vec!["print", "(", "42", ")", ";"]);
+}
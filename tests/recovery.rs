@@ -0,0 +1,74 @@
+use lab1::Par;
+
+// the line of every syntax error the parser finds in `source`
+fn problem_lines(source: &str) -> Vec<usize> {
+    let mut par = Par::from_str(source);
+    par.parse();
+    par.problems.iter().map(|err| err.span.start.line).collect()
+}
+
+#[test]
+fn every_broken_statement_is_reported() {
+    let source = "func main() {
+    int a;
+    a = ;
+    print(a));
+    int b = 2
+    read(a b);
+    while a < { b = 1; }
+    print(b);
+}
+func other(int x) {
+    return x +;
+}
+";
+    assert_eq!(problem_lines(source), vec![3, 4, 6, 7, 11]);
+}
+
+#[test]
+fn unclosed_body_is_reported_once() {
+    assert_eq!(problem_lines("func main() {\n    int a = 1;\n"), vec![3]);
+}
+
+#[test]
+fn problems_say_what_was_expected_and_found() {
+    let mut par = Par::from_str("func main() {\n    a = 1\n}\nfunc f(int x y) {}\n");
+    par.parse();
+    let msgs: Vec<&str> = par.problems.iter().map(|err| err.msg.as_str()).collect();
+    assert_eq!(msgs, vec![
+        "expected `;` after assignment, found `}`",
+        "expected `,` or `)` after parameter, found `y`",
+    ]);
+}
+
+#[test]
+fn only_the_negated_literal_may_be_out_of_range() {
+    let mut par = Par::from_str("func main() {\n    print(-2147483648);\n    print(2147483648);\n}\n");
+    par.parse();
+    let msgs: Vec<&str> = par.problems.iter().map(|err| err.msg.as_str()).collect();
+    assert_eq!(msgs, vec!["integer literal 2147483648 does not fit in a 32-bit int, only its negation does"]);
+    assert_eq!(problem_lines("func main() {\n    print(-2147483648);\n}\n"), Vec::<usize>::new());
+    // the token itself keeps the value that was written
+    assert!(matches!(lab1::Lex::from_str("0x80000000").next().tok, lab1::Tok::Number(2147483648)));
+}
+
+#[test]
+fn globals_after_a_broken_function_are_still_checked() {
+    let source = "func f(int x y, int z) {
+    int a;
+}
+int g = ;
+const K = ;
+int ok;
+func main() {}
+";
+    assert_eq!(problem_lines(source), vec![1, 4, 5]);
+}
+
+#[test]
+fn a_second_default_is_reported() {
+    let mut par = Par::from_str("func main() {\n    switch 1 {\n        default:\n        default:\n    }\n}\n");
+    par.parse();
+    let msgs: Vec<&str> = par.problems.iter().map(|err| err.msg.as_str()).collect();
+    assert_eq!(msgs, vec!["a switch can only have one `default`"]);
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+use std::process::Command;
+
+// Run the real binary on `input` and compare what it printed with `golden`, or rewrite
+// `golden` when UPDATE_SAMPLES is set. Returns whether the output was unchanged.
+fn matches_golden(input: &Path, emit: Option<&str>, golden: &Path) -> bool {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_lab1"));
+    if let Some(emit) = emit {
+        command.arg(format!("--emit={}", emit));
+    }
+    let output = command.arg(input).output().unwrap();
+    if std::env::var_os("UPDATE_SAMPLES").is_some() {
+        std::fs::write(golden, &output.stdout).unwrap();
+        return true;
+    }
+    std::fs::read(golden).ok().as_deref() == Some(&output.stdout[..])
+}
+
+// Every sample program at the top of the repo, compiled by the real binary and compared with
+// what it printed when the output was last reviewed. Run with UPDATE_SAMPLES=1 to rewrite
+// tests/samples/*.out after a deliberate change to the IR or to a message.
+#[test]
+fn sample_programs_print_the_reviewed_output() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut samples: Vec<_> = std::fs::read_dir(root).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt") && !path.ends_with("GRAMMAR.txt"))
+        .collect();
+    samples.sort();
+    assert!(!samples.is_empty());
+
+    let mut changed = Vec::new();
+    for sample in &samples {
+        let name = sample.file_stem().unwrap().to_string_lossy().to_string();
+        let golden = root.join("tests/samples").join(format!("{}.out", name));
+        if !matches_golden(sample, None, &golden) {
+            changed.push(name);
+        }
+    }
+    assert!(changed.is_empty(), "output changed for {:?}", changed);
+}
+
+// the token dumps, for a sample that compiles and for one with lexer problems
+#[test]
+fn token_dumps_print_the_reviewed_output() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/samples");
+    let strings = Path::new(env!("CARGO_MANIFEST_DIR")).join("strings.txt");
+    let errors = dir.join("lex_errors.txt");
+    let mut changed = Vec::new();
+    for (input, name) in [(&strings, "strings"), (&errors, "lex_errors")] {
+        for (emit, ext) in [("tokens", "tokens"), ("tokens-json", "json")] {
+            if !matches_golden(input, Some(emit), &dir.join(format!("{}.{}", name, ext))) {
+                changed.push(format!("{} --emit={}", name, emit));
+            }
+        }
+    }
+    assert!(changed.is_empty(), "output changed for {:?}", changed);
+}
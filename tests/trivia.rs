@@ -0,0 +1,28 @@
+use lab1::{Lex, Tok};
+
+// put the source back together from a lossless token stream
+fn rebuild(source: &str) -> String {
+    let mut lex = Lex::from_str(source).lossless();
+    let mut out = Vec::new();
+    loop {
+        let token = lex.next();
+        let trivia = token.trivia.expect("lossless lexer gives every token trivia");
+        out.extend(trivia.leading);
+        out.extend(trivia.text);
+        out.extend(trivia.trailing);
+        if let Tok::Empty = token.tok { break; }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn function_txt_round_trips() {
+    let source = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/function.txt")).unwrap();
+    assert_eq!(rebuild(&source), source);
+}
+
+#[test]
+fn comments_strings_and_errors_round_trip() {
+    let source = "# header\r\nfunc main() { /* a /* nested */ one */\n\tprint(\"hi\\n\"); # done\n  x = 0x1F $ 1_0;\r}\n/* open";
+    assert_eq!(rebuild(source), source);
+}